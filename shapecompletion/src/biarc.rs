@@ -0,0 +1,270 @@
+use visioniechor::{PointF64, Spline};
+
+use crate::geo::{cross_product, hermite_to_cubic_bezier, rotate_vector, signed_angle_between};
+
+/// A circular arc from 'from_point' to 'to_point' leaving 'from_point' in 'from_direction'.
+/// It is a straight line segment if 'from_direction' points at 'to_point'.
+#[derive(Clone, Copy, Debug)]
+pub struct CircularArc {
+    pub from_point: PointF64,
+    /// Unit vector
+    pub from_direction: PointF64,
+    pub to_point: PointF64,
+}
+
+// API
+impl CircularArc {
+    /// Connect 'from_point' and 'to_point' by two tangent-continuous circular arcs (a biarc), such that
+    /// the curve leaves 'from_point' in 'from_direction' and arrives at 'to_point' in 'to_direction'.
+    /// The two arcs have equal tangent lengths.
+    /// 'curvatures' are the signed curvatures of the curves continued at 'from_point' and 'to_point',
+    /// in the direction of travel.
+    /// If both directions make the same angle with the base line (within 'single_arc_tolerance' radians),
+    /// a single circle fits both. Only 1 arc is returned then, unless 'curvatures' is Some and any of them
+    /// differs from the curvature of that circle by more than 'single_arc_tolerance' over the base line.
+    /// Return None if no biarc with positive tangent lengths exists.
+    pub fn fit_biarc(
+        from_point: PointF64,
+        from_direction: PointF64,
+        to_point: PointF64,
+        to_direction: PointF64,
+        curvatures: Option<(f64, f64)>,
+        single_arc_tolerance: f64,
+    ) -> Option<Vec<Self>> {
+        let (t0, t1) = (
+            from_direction.get_normalized(),
+            to_direction.get_normalized(),
+        );
+        let v = to_point - from_point;
+        if v.norm() < f64::EPSILON {
+            return None;
+        }
+
+        // Curvatures of the circles through both endpoints, implied by each direction
+        let from_angle = signed_angle_between(t0, v);
+        let to_angle = signed_angle_between(v, t1);
+        if (from_angle - to_angle).abs() <= single_arc_tolerance {
+            let arc = Self::new(from_point, t0, to_point);
+            let agrees_with = |curvature: f64| {
+                (curvature - arc.curvature()).abs() * v.norm() <= single_arc_tolerance
+            };
+            let curvatures_agree = curvatures.is_none_or(|(from_curvature, to_curvature)| {
+                agrees_with(from_curvature) && agrees_with(to_curvature)
+            });
+            if curvatures_agree {
+                return Some(vec![arc]);
+            }
+        }
+
+        // Solve |(p1 - d * t1) - (p0 + d * t0)| = 2d for the tangent length d
+        let t = t0 + t1;
+        let v_dot_t = v.dot(t);
+        let denom = 2.0 * (1.0 - t0.dot(t1));
+        let d = if denom.abs() < f64::EPSILON {
+            // Parallel directions
+            let v_dot_t1 = v.dot(t1);
+            if v_dot_t1 <= f64::EPSILON {
+                return None;
+            }
+            v.dot(v) / (4.0 * v_dot_t1)
+        } else {
+            (-v_dot_t + (v_dot_t * v_dot_t + denom * v.dot(v)).sqrt()) / denom
+        };
+        if d <= f64::EPSILON {
+            return None;
+        }
+
+        let (control_point1, control_point2) = (from_point + t0 * d, to_point - t1 * d);
+        let junction_point = (control_point1 + control_point2) / 2.0;
+        let junction_direction = (control_point2 - control_point1).get_normalized();
+
+        Some(vec![
+            Self::new(from_point, t0, junction_point),
+            Self::new(junction_point, junction_direction, to_point),
+        ])
+    }
+
+    pub fn new(from_point: PointF64, from_direction: PointF64, to_point: PointF64) -> Self {
+        Self {
+            from_point,
+            from_direction,
+            to_point,
+        }
+    }
+
+    /// Signed curvature, positive if the arc turns in the direction of positive angles
+    pub fn curvature(&self) -> f64 {
+        let chord = self.to_point - self.from_point;
+        2.0 * cross_product(self.from_direction, chord) / chord.dot(chord)
+    }
+
+    /// Signed angle swept by the arc
+    pub fn sweep_angle(&self) -> f64 {
+        2.0 * signed_angle_between(self.from_direction, self.to_point - self.from_point)
+    }
+
+    /// Convert the arc to cubic bezier curves, each of which sweeps at most 'max_sweep_angle' radians.
+    /// Each spline in the returned vector contains exactly 1 curve.
+    pub fn to_splines(&self, max_sweep_angle: f64) -> Vec<Spline> {
+        let curvature = self.curvature();
+        let chord = self.to_point - self.from_point;
+        if curvature.abs() * chord.norm() < f64::EPSILON {
+            // Straight line
+            return vec![hermite_to_cubic_bezier(
+                self.from_point,
+                chord,
+                self.to_point,
+                chord,
+            )];
+        }
+
+        let sweep_angle = self.sweep_angle();
+        let num_pieces = std::cmp::max((sweep_angle.abs() / max_sweep_angle).ceil() as usize, 1);
+        let piece_angle = sweep_angle / num_pieces as f64;
+
+        let radius = 1.0 / curvature.abs();
+        let center = self.from_point
+            + rotate_vector(self.from_direction, std::f64::consts::FRAC_PI_2) / curvature;
+        let radial = self.from_point - center;
+        // Length of the derivatives for the standard bezier approximation of an arc
+        let derivative_length = 4.0 * (piece_angle.abs() / 4.0).tan() * radius;
+
+        (0..num_pieces)
+            .map(|i| {
+                let (angle1, angle2) = (i as f64 * piece_angle, (i + 1) as f64 * piece_angle);
+                let point1 = if i == 0 {
+                    self.from_point
+                } else {
+                    center + rotate_vector(radial, angle1)
+                };
+                let point2 = if i + 1 == num_pieces {
+                    self.to_point
+                } else {
+                    center + rotate_vector(radial, angle2)
+                };
+                hermite_to_cubic_bezier(
+                    point1,
+                    rotate_vector(self.from_direction, angle1) * derivative_length,
+                    point2,
+                    rotate_vector(self.from_direction, angle2) * derivative_length,
+                )
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn points_approximately(a: PointF64, b: PointF64) -> bool {
+        a.distance_to(b) <= 1e-6
+    }
+
+    /// Unit tangent where 'arc' arrives at its 'to_point'
+    fn arriving_direction(arc: &CircularArc) -> PointF64 {
+        rotate_vector(arc.from_direction, arc.sweep_angle())
+    }
+
+    #[test]
+    fn biarc_interpolates_endpoints_and_directions() {
+        // GIVEN
+        let (from_point, to_point) = (PointF64::new(3.0, 4.0), PointF64::new(25.0, 11.0));
+        let from_direction = PointF64::new(1.0, -1.0).get_normalized();
+        let to_direction = PointF64::new(0.6, 0.8);
+
+        // WHEN
+        let arcs = CircularArc::fit_biarc(
+            from_point,
+            from_direction,
+            to_point,
+            to_direction,
+            None,
+            1e-6,
+        )
+        .unwrap();
+
+        // THEN
+        assert_eq!(arcs.len(), 2);
+        assert!(points_approximately(arcs[0].from_point, from_point));
+        assert!(points_approximately(arcs[0].from_direction, from_direction));
+        assert!(points_approximately(arcs[1].to_point, to_point));
+        assert!(points_approximately(
+            arriving_direction(&arcs[1]),
+            to_direction
+        ));
+        // Tangent-continuous at the junction
+        assert!(points_approximately(arcs[0].to_point, arcs[1].from_point));
+        assert!(points_approximately(
+            arriving_direction(&arcs[0]),
+            arcs[1].from_direction
+        ));
+    }
+
+    #[test]
+    fn biarc_splines_keep_endpoints_and_directions() {
+        // GIVEN
+        let (from_point, to_point) = (PointF64::new(0.0, 0.0), PointF64::new(20.0, 5.0));
+        let (from_direction, to_direction) = (PointF64::new(0.0, 1.0), PointF64::new(0.0, -1.0));
+        let arcs = CircularArc::fit_biarc(
+            from_point,
+            from_direction,
+            to_point,
+            to_direction,
+            None,
+            1e-6,
+        )
+        .unwrap();
+
+        // WHEN
+        let splines: Vec<Spline> = arcs
+            .iter()
+            .flat_map(|arc| arc.to_splines(std::f64::consts::FRAC_PI_4))
+            .collect();
+
+        // THEN
+        let first = splines[0].get_control_points()[0].to_vec();
+        let last = splines[splines.len() - 1].get_control_points()[0].to_vec();
+        assert!(points_approximately(first[0], from_point));
+        assert!(points_approximately(
+            (first[1] - first[0]).get_normalized(),
+            from_direction
+        ));
+        assert!(points_approximately(last[3], to_point));
+        assert!(points_approximately(
+            (last[3] - last[2]).get_normalized(),
+            to_direction
+        ));
+    }
+
+    #[test]
+    fn biarc_reduces_to_single_arc_only_when_curvatures_agree() {
+        // GIVEN
+        // Both directions are tangent to the circle of radius 10 centred at (0, 0)
+        let (from_point, to_point) = (PointF64::new(10.0, 0.0), PointF64::new(0.0, 10.0));
+        let (from_direction, to_direction) = (PointF64::new(0.0, 1.0), PointF64::new(-1.0, 0.0));
+        let fit = |curvatures| {
+            CircularArc::fit_biarc(
+                from_point,
+                from_direction,
+                to_point,
+                to_direction,
+                curvatures,
+                0.05,
+            )
+            .unwrap()
+        };
+
+        // WHEN
+        let without_curvatures = fit(None);
+        let agreeing = fit(Some((0.1, 0.1)));
+        let disagreeing = fit(Some((0.1, 0.0)));
+
+        // THEN
+        assert_eq!(without_curvatures.len(), 1);
+        assert_eq!(agreeing.len(), 1);
+        assert!((agreeing[0].curvature() - 0.1).abs() < 1e-9);
+        assert_eq!(disagreeing.len(), 2);
+        assert!(points_approximately(disagreeing[1].to_point, to_point));
+    }
+}
@@ -0,0 +1,212 @@
+use visioniechor::{PathF64, PointF64, Spline};
+
+use crate::geo::{hermite_to_cubic_bezier, normalize_angle};
+
+/// Number of (even) intervals used in Simpson's rule
+const NUM_QUADRATURE_INTERVALS: usize = 64;
+const MAX_NEWTON_ITERATIONS: usize = 32;
+const NEWTON_TOLERANCE: f64 = 1e-10;
+
+/// A segment of an Euler spiral (clothoid), a curve whose curvature varies linearly with arc length.
+/// The curvature at arc length s is 'curvature + curvature_rate * s'.
+#[derive(Clone, Copy, Debug)]
+pub struct Clothoid {
+    pub start_point: PointF64,
+    /// Inclined angle of the direction at 'start_point'
+    pub start_angle: f64,
+    pub curvature: f64,
+    pub curvature_rate: f64,
+    pub length: f64,
+}
+
+// API
+impl Clothoid {
+    /// Fit the clothoid which leaves 'from_point' in 'from_direction' and arrives at 'to_point' in 'to_direction'.
+    /// This is the G1 Hermite interpolation problem solved as in Bertolazzi and Frego (2015).
+    /// Return None if the solver does not converge.
+    pub fn fit_g1(
+        from_point: PointF64,
+        from_direction: PointF64,
+        to_point: PointF64,
+        to_direction: PointF64,
+    ) -> Option<Self> {
+        let base = to_point - from_point;
+        let base_length = base.norm();
+        if base_length < f64::EPSILON {
+            return None;
+        }
+
+        // Angles relative to the base line
+        let base_angle = base.y.atan2(base.x);
+        let phi0 = normalize_angle(from_direction.y.atan2(from_direction.x) - base_angle);
+        let phi1 = normalize_angle(to_direction.y.atan2(to_direction.x) - base_angle);
+        let delta = phi1 - phi0;
+
+        // Solve Y(a) = 0 for the half curvature rate 'a' of the normalized problem by Newton's method
+        let phase = |a: f64, t: f64| a * t * t + (delta - a) * t + phi0;
+        let mut a = 3.0 * (phi0 + phi1);
+        let mut converged = false;
+        for _ in 0..MAX_NEWTON_ITERATIONS {
+            let y = Self::integrate(|t| phase(a, t).sin());
+            let dy = Self::integrate(|t| phase(a, t).cos() * (t * t - t));
+            if dy.abs() < f64::EPSILON {
+                break;
+            }
+            let step = y / dy;
+            a -= step;
+            if step.abs() < NEWTON_TOLERANCE {
+                converged = true;
+                break;
+            }
+        }
+        if !converged {
+            return None;
+        }
+
+        let x = Self::integrate(|t| phase(a, t).cos());
+        if x <= f64::EPSILON {
+            return None;
+        }
+
+        let length = base_length / x;
+        Some(Self {
+            start_point: from_point,
+            start_angle: base_angle + phi0,
+            curvature: (delta - a) / length,
+            curvature_rate: 2.0 * a / length / length,
+            length,
+        })
+    }
+
+    pub fn angle_at(&self, s: f64) -> f64 {
+        self.start_angle + self.curvature * s + 0.5 * self.curvature_rate * s * s
+    }
+
+    pub fn curvature_at(&self, s: f64) -> f64 {
+        self.curvature + self.curvature_rate * s
+    }
+
+    /// Unit vector in the direction of the curve at arc length 's'
+    pub fn direction_at(&self, s: f64) -> PointF64 {
+        let angle = self.angle_at(s);
+        PointF64::new(angle.cos(), angle.sin())
+    }
+
+    pub fn point_at(&self, s: f64) -> PointF64 {
+        let dx = s * Self::integrate(|t| self.angle_at(t * s).cos());
+        let dy = s * Self::integrate(|t| self.angle_at(t * s).sin());
+        self.start_point + PointF64::new(dx, dy)
+    }
+
+    /// Sample the curve into a polyline, whose consecutive points are at most 'max_step' apart along the curve.
+    pub fn to_path(&self, max_step: f64) -> PathF64 {
+        let num_steps = std::cmp::max((self.length / max_step).ceil() as usize, 1);
+        PathF64::from_points(
+            (0..=num_steps)
+                .map(|i| self.point_at(self.length * i as f64 / num_steps as f64))
+                .collect(),
+        )
+    }
+
+    /// Approximate the curve by cubic bezier curves, each of which turns by at most about 'max_turning' radians.
+    /// Each spline in the returned vector contains exactly 1 curve.
+    pub fn to_splines(&self, max_turning: f64) -> Vec<Spline> {
+        let max_abs_curvature = f64::max(
+            self.curvature_at(0.0).abs(),
+            self.curvature_at(self.length).abs(),
+        );
+        let num_pieces = std::cmp::max(
+            (max_abs_curvature * self.length / max_turning).ceil() as usize,
+            1,
+        );
+        let piece_length = self.length / num_pieces as f64;
+
+        (0..num_pieces)
+            .map(|i| {
+                let (s1, s2) = (i as f64 * piece_length, (i + 1) as f64 * piece_length);
+                hermite_to_cubic_bezier(
+                    self.point_at(s1),
+                    self.direction_at(s1) * piece_length,
+                    self.point_at(s2),
+                    self.direction_at(s2) * piece_length,
+                )
+            })
+            .collect()
+    }
+}
+
+// Helper functions
+impl Clothoid {
+    /// Integrate 'f' over [0, 1] by Simpson's rule.
+    fn integrate<F>(f: F) -> f64
+    where
+        F: Fn(f64) -> f64,
+    {
+        let n = NUM_QUADRATURE_INTERVALS;
+        let h = 1.0 / n as f64;
+        let sum: f64 = (0..=n)
+            .map(|i| {
+                let weight = if i == 0 || i == n {
+                    1.0
+                } else if i % 2 == 1 {
+                    4.0
+                } else {
+                    2.0
+                };
+                weight * f(i as f64 * h)
+            })
+            .sum();
+        sum * h / 3.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn points_approximately(a: PointF64, b: PointF64) -> bool {
+        a.distance_to(b) <= 1e-6
+    }
+
+    #[test]
+    fn clothoid_fit_g1_interpolates_endpoints_and_directions() {
+        // GIVEN
+        let (from_point, to_point) = (PointF64::new(3.0, 4.0), PointF64::new(25.0, 11.0));
+        let from_direction = PointF64::new(1.0, -1.0).get_normalized();
+        let to_direction = PointF64::new(0.6, 0.8);
+
+        // WHEN
+        let clothoid =
+            Clothoid::fit_g1(from_point, from_direction, to_point, to_direction).unwrap();
+
+        // THEN
+        assert!(points_approximately(clothoid.point_at(0.0), from_point));
+        assert!(points_approximately(
+            clothoid.point_at(clothoid.length),
+            to_point
+        ));
+        assert!(points_approximately(
+            clothoid.direction_at(0.0),
+            from_direction
+        ));
+        assert!(points_approximately(
+            clothoid.direction_at(clothoid.length),
+            to_direction
+        ));
+    }
+
+    #[test]
+    fn clothoid_fit_g1_reduces_to_circular_arc() {
+        // GIVEN a quarter circle of radius 10
+        let (from_point, to_point) = (PointF64::new(10.0, 0.0), PointF64::new(0.0, 10.0));
+        let (from_direction, to_direction) = (PointF64::new(0.0, 1.0), PointF64::new(-1.0, 0.0));
+
+        // WHEN
+        let clothoid =
+            Clothoid::fit_g1(from_point, from_direction, to_point, to_direction).unwrap();
+
+        // THEN
+        assert!(clothoid.curvature_rate.abs() <= 1e-6);
+        assert!((clothoid.curvature - 0.1).abs() <= 1e-6);
+    }
+}
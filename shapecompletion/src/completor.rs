@@ -1,26 +1,115 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 use bit_vec::BitVec;
 use visioniechor::{
-    clusters::Cluster, BinaryImage, BoundingRect, Color, ColorName, CompoundPath,
+    clusters::Cluster, BinaryImage, BoundingRect, Color, ColorImage, ColorName, CompoundPath,
     CompoundPathElement, PathI32, PointI32,
 };
 
 use crate::{
-    curve::{CurveIntrapolator, CurveIntrapolatorConfig},
+    curve::{CurveIntrapolator, CurveIntrapolatorConfig, IntrapolatedCurve},
+    curve_model::CurveModel,
     debugger::{Debugger, DummyDebugger},
-    filler::{FilledHoleMatrix, HoleFiller},
-    geo::bezier_curves_intersection,
-    matcher::Matcher,
+    diffusion::{DiffusionConfig, DiffusionFiller},
+    filler::{ApplyConfig, CoverageMatrix, FillMode, FilledHoleMatrix, HoleFiller},
+    geo::{
+        bezier_curves_intersection, curves_closer_than, reverse_compound_path,
+        sample_compound_path, CLEARANCE_SAMPLING_RATIO,
+    },
+    matcher::{ExhaustivePartitionStrategy, MatchingStrategy},
     matcher_helper::{MatchItem, MatchItemSet, Matching},
+    refinement::{CurveRefiner, RefinementConfig},
+    symmetry::{ReflectionAxis, SymmetryCompletor, SymmetryConfig},
+    tail::TailAnalyzer,
+    texture::{TextureSynthesisConfig, TextureSynthesizer},
 };
 
+/// How the parameters in pixels (or numbers of pixels) are interpreted
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ParameterScale {
+    /// As they are given
+    Absolute,
+    /// As tuned for a hole whose larger side is 'reference_hole_size' pixels long, on contours whose path segments
+    /// have a median length of 'reference_segment_length' pixels.
+    /// They are scaled by the geometric mean of the ratios of the actual sizes to these reference sizes.
+    Relative {
+        reference_hole_size: f64,
+        reference_segment_length: f64,
+    },
+}
+
+impl ParameterScale {
+    /// The factor by which the parameters are multiplied for 'hole_rect', whose surrounding contours
+    /// are split into the unsimplified 'raw_path_segments'.
+    pub fn factor(&self, hole_rect: &BoundingRect, raw_path_segments: &[PathI32]) -> f64 {
+        match *self {
+            Self::Absolute => 1.0,
+            Self::Relative {
+                reference_hole_size,
+                reference_segment_length,
+            } => {
+                let hole_size = std::cmp::max(hole_rect.width(), hole_rect.height()) as f64;
+                let mut segment_lengths: Vec<f64> = raw_path_segments
+                    .iter()
+                    .map(|segment| {
+                        segment
+                            .path
+                            .windows(2)
+                            .map(|point_pair| {
+                                point_pair[0]
+                                    .to_point_f64()
+                                    .distance_to(point_pair[1].to_point_f64())
+                            })
+                            .sum()
+                    })
+                    .collect();
+                segment_lengths.sort_by(|a, b| a.total_cmp(b));
+                // Without contours, only the hole size is taken into account
+                let segment_length_ratio = segment_lengths
+                    .get(segment_lengths.len() / 2)
+                    .map_or(1.0, |median| median / reference_segment_length);
+                (hole_size / reference_hole_size * segment_length_ratio).sqrt()
+            }
+        }
+    }
+}
+
+/// A completed hole, with how trustworthy the completion is
+pub struct CompletionResult {
+    pub filled_hole: FilledHoleMatrix,
+    /// The confidences in [0.0, 1.0] of the intrapolated curves, in the order of the matched pairs of endpoints.
+    /// Empty if no curve is intrapolated.
+    pub curve_confidences: Vec<f64>,
+    /// In [0.0, 1.0], the lowest of 'curve_confidences',
+    /// or the score of the reflection axis if the hole is completed by symmetry,
+    /// or 1.0 if there is nothing to complete
+    pub confidence: f64,
+    /// The axis about which the visible image is reflected into the hole, if the hole is completed by symmetry
+    pub reflection_axis: Option<ReflectionAxis>,
+}
+
+/// The parameters in effect for a single hole, after scaling
+#[derive(Clone, Copy)]
+struct HoleParameters {
+    simplify_tolerance: f64,
+    curve_intrapolator_config: CurveIntrapolatorConfig,
+    filler_blank_boundary_pixels_tolerance: usize,
+    symmetry_config: Option<SymmetryConfig>,
+}
+
 pub struct ShapeCompletor {
     image: BinaryImage,
     simplify_tolerance: f64,
     curve_intrapolator_config: CurveIntrapolatorConfig,
     filler_blank_boundary_pixels_tolerance: usize,
+    matching_strategy: Box<dyn MatchingStrategy>,
+    /// The curve model of 'curve_intrapolator_config' (after scaling) is used if None
+    curve_model: Option<Box<dyn CurveModel>>,
     debugger: Box<dyn Debugger>,
+    /// The symmetry stage is skipped if None
+    symmetry_config: Option<SymmetryConfig>,
+    parameter_scale: ParameterScale,
+    fill_mode: FillMode,
 }
 
 // API
@@ -37,10 +126,48 @@ impl ShapeCompletor {
             simplify_tolerance,
             curve_intrapolator_config,
             filler_blank_boundary_pixels_tolerance,
+            matching_strategy: Box::new(ExhaustivePartitionStrategy),
+            curve_model: None,
             debugger: debugger.unwrap_or_else(|| Box::new(DummyDebugger)),
+            symmetry_config: None,
+            parameter_scale: ParameterScale::Absolute,
+            fill_mode: FillMode::BoundarySampling,
         }
     }
 
+    /// Find the candidate matchings of endpoints with 'matching_strategy' instead of 'ExhaustivePartitionStrategy'.
+    pub fn with_matching_strategy(mut self, matching_strategy: Box<dyn MatchingStrategy>) -> Self {
+        self.matching_strategy = matching_strategy;
+        self
+    }
+
+    /// Bridge the gaps with 'curve_model' instead of the curve model selected by the curve intrapolator config.
+    pub fn with_curve_model(mut self, curve_model: Box<dyn CurveModel>) -> Self {
+        self.curve_model = Some(curve_model);
+        self
+    }
+
+    /// Interpret the parameters relative to the size of each hole and its surrounding contours, if 'parameter_scale'
+    /// is 'ParameterScale::Relative', so that completing a resized image gives a correspondingly resized result.
+    pub fn with_parameter_scale(mut self, parameter_scale: ParameterScale) -> Self {
+        self.parameter_scale = parameter_scale;
+        self
+    }
+
+    /// Enable the symmetry stage, which completes the hole by reflecting the visible image
+    /// if the visible shape is mirror-symmetric. Curve intrapolation is the fallback.
+    pub fn with_symmetry_config(mut self, symmetry_config: SymmetryConfig) -> Self {
+        self.symmetry_config = Some(symmetry_config);
+        self
+    }
+
+    /// Choose how the regions inside the hole are filled after curve intrapolation.
+    /// 'filler_blank_boundary_pixels_tolerance' is only used by 'FillMode::BoundarySampling'.
+    pub fn with_fill_mode(mut self, fill_mode: FillMode) -> Self {
+        self.fill_mode = fill_mode;
+        self
+    }
+
     pub fn complete_shape_and_draw(&self, hole_rect: BoundingRect) -> Result<(), String> {
         let hole_origin = PointI32::new(hole_rect.left, hole_rect.top);
         let filled_hole = self.complete_shape(hole_rect)?;
@@ -114,29 +241,159 @@ impl ShapeCompletor {
     }
 
     pub fn complete_shape(&self, hole_rect: BoundingRect) -> Result<FilledHoleMatrix, String> {
+        self.complete_shape_with_report(hole_rect)
+            .map(|result| result.filled_hole)
+    }
+
+    /// Return a copy of the image with the completion written into the hole as specified by 'apply_config'.
+    pub fn complete_shape_into_image(
+        &self,
+        hole_rect: BoundingRect,
+        apply_config: &ApplyConfig,
+    ) -> Result<BinaryImage, String> {
+        let hole_origin = PointI32::new(hole_rect.left, hole_rect.top);
+        self.complete_shape(hole_rect).map(|filled_hole| {
+            filled_hole.applied_to_binary_image(&self.image, hole_origin, apply_config)
+        })
+    }
+
+    /// Return a copy of 'color_image' with the pattern of the shape synthesized into the hole,
+    /// where the image given to the completor marks the shape in 'color_image'.
+    /// The behavior is undefined unless 'color_image' and the image given to the completor are of the same size.
+    pub fn complete_shape_with_texture(
+        &self,
+        hole_rect: BoundingRect,
+        color_image: &ColorImage,
+        texture_synthesis_config: TextureSynthesisConfig,
+    ) -> Result<ColorImage, String> {
+        let hole_origin = PointI32::new(hole_rect.left, hole_rect.top);
+        let filled_hole = self.complete_shape(hole_rect)?;
+
+        //# Texture synthesis
+        Ok(
+            TextureSynthesizer::new(texture_synthesis_config).synthesize(
+                color_image,
+                &self.image,
+                &filled_hole,
+                hole_origin,
+            ),
+        )
+    }
+
+    /// Shade the hole in the grayscale image 'pixels' in place by harmonic interpolation inside each region of the
+    /// completed shape, where 'pixels' is stored row by row and the image given to the completor marks the shape in it.
+    /// The behavior is undefined unless 'pixels' and the image given to the completor are of the same size.
+    pub fn complete_shape_with_diffusion(
+        &self,
+        hole_rect: BoundingRect,
+        pixels: &mut [u8],
+        diffusion_config: DiffusionConfig,
+    ) -> Result<(), String> {
+        let hole_origin = PointI32::new(hole_rect.left, hole_rect.top);
+        let filled_hole = self.complete_shape(hole_rect)?;
+
+        //# Diffusion
+        DiffusionFiller::new(diffusion_config).fill(pixels, &self.image, &filled_hole, hole_origin);
+        Ok(())
+    }
+
+    /// Same as 'complete_shape', but also report the confidence of the completion,
+    /// so that low-confidence holes can be sent to review.
+    pub fn complete_shape_with_report(
+        &self,
+        hole_rect: BoundingRect,
+    ) -> Result<CompletionResult, String> {
+        self.complete_shape_supersampled(hole_rect, 1)
+    }
+
+    /// Same as 'complete_shape', but return the fraction of each pixel covered by the foreground,
+    /// by filling the hole at 'supersampling' times the resolution, so that the completion can be composited
+    /// into anti-aliased images. Holes completed by symmetry are not anti-aliased.
+    /// The behavior is undefined unless 'supersampling' is positive.
+    pub fn complete_shape_with_coverage(
+        &self,
+        hole_rect: BoundingRect,
+        supersampling: usize,
+    ) -> Result<CoverageMatrix, String> {
+        self.complete_shape_supersampled(hole_rect, supersampling)
+            .map(|result| match result.reflection_axis {
+                Some(_) => CoverageMatrix::from_reflected_hole(&result.filled_hole, supersampling),
+                None => CoverageMatrix::from_filled_hole(&result.filled_hole, supersampling),
+            })
+    }
+}
+
+// Helper functions
+impl ShapeCompletor {
+    /// Same as 'complete_shape_with_report', but the filled hole is 'supersampling' times as wide and as high as
+    /// 'hole_rect'.
+    fn complete_shape_supersampled(
+        &self,
+        hole_rect: BoundingRect,
+        supersampling: usize,
+    ) -> Result<CompletionResult, String> {
         //# Path walking
         let paths = self.get_test_paths();
 
-        //# Path identification, segmentation, and simplification
-        let path_segments = self.find_simplified_segments_from_paths(&hole_rect, paths);
+        //# Path identification and segmentation
+        let closed_paths = match self.fill_mode {
+            FillMode::BoundarySampling => vec![],
+            FillMode::EvenOdd => Self::find_paths_away_from_hole(&hole_rect, &paths),
+        };
+        let raw_path_segments = self.find_segments_from_paths(&hole_rect, paths);
+
+        //# Parameter scaling
+        let parameters = self.calculate_hole_parameters(&hole_rect, &raw_path_segments);
+
+        //# Symmetry
+        if let Some(symmetry_config) = parameters.symmetry_config {
+            if let Some((filled_hole, axis)) =
+                SymmetryCompletor::new(symmetry_config).complete(&self.image, hole_rect)
+            {
+                return Ok(CompletionResult {
+                    filled_hole: filled_hole.new_upscaled(supersampling),
+                    curve_confidences: vec![],
+                    confidence: axis.score,
+                    reflection_axis: Some(axis),
+                });
+            }
+        }
+
+        //# Path simplification
+        let path_segments: Vec<PathI32> = raw_path_segments
+            .iter()
+            .map(|segment| Self::simplify_segment(segment, parameters.simplify_tolerance))
+            .collect();
 
         if path_segments.is_empty() {
-            return Ok(FilledHoleMatrix::new(
-                hole_rect.width() as usize,
-                hole_rect.height() as usize,
-            ));
+            return Ok(CompletionResult {
+                filled_hole: FilledHoleMatrix::new(
+                    hole_rect.width() as usize * supersampling,
+                    hole_rect.height() as usize * supersampling,
+                ),
+                curve_confidences: vec![],
+                confidence: 1.0,
+                reflection_axis: None,
+            });
         }
 
         //# Matching paths
-        let match_item_set = self.construct_match_item_set(&path_segments)?;
-        let matchings = Matcher::find_all_possible_matchings(match_item_set)?;
+        let match_item_set = self.construct_match_item_set(
+            &hole_rect,
+            &path_segments,
+            &raw_path_segments,
+            parameters.curve_intrapolator_config,
+        )?;
+        let matchings = self.matching_strategy.find_matchings(match_item_set)?;
 
-        let intrapolated_curves = {
+        let (intrapolated_curves, curve_confidences) = {
             let try_intrapolation = |correct_tail_tangents| {
                 self.try_intrapolate_with_matchings(
                     hole_rect,
                     &matchings,
                     &path_segments,
+                    &raw_path_segments,
+                    parameters.curve_intrapolator_config,
                     correct_tail_tangents,
                 )
             };
@@ -147,23 +404,62 @@ impl ShapeCompletor {
             }
         };
 
-        let endpoints: Vec<PointI32> = path_segments
-            .into_iter()
-            .map(|segment| segment[0])
-            .collect();
+        //# Filling
+        let filled_hole = match self.fill_mode {
+            FillMode::BoundarySampling => {
+                let endpoints: Vec<PointI32> = path_segments
+                    .into_iter()
+                    .map(|segment| segment[0])
+                    .collect();
+                HoleFiller::fill_supersampled(
+                    &self.image,
+                    hole_rect,
+                    intrapolated_curves,
+                    endpoints,
+                    parameters.filler_blank_boundary_pixels_tolerance,
+                    supersampling,
+                )?
+            }
+            FillMode::EvenOdd => {
+                let mut visible_contours = Self::remove_reversed_segments(raw_path_segments);
+                visible_contours.extend(closed_paths);
+                HoleFiller::fill_by_even_odd(
+                    hole_rect,
+                    intrapolated_curves,
+                    &visible_contours,
+                    supersampling,
+                )
+            }
+        };
 
-        HoleFiller::fill(
-            &self.image,
-            hole_rect,
-            intrapolated_curves,
-            endpoints,
-            self.filler_blank_boundary_pixels_tolerance,
-        )
+        let confidence = curve_confidences.iter().copied().fold(1.0, f64::min);
+        Ok(CompletionResult {
+            filled_hole,
+            curve_confidences,
+            confidence,
+            reflection_axis: None,
+        })
+    }
+
+    fn calculate_hole_parameters(
+        &self,
+        hole_rect: &BoundingRect,
+        raw_path_segments: &[PathI32],
+    ) -> HoleParameters {
+        let factor = self.parameter_scale.factor(hole_rect, raw_path_segments);
+        HoleParameters {
+            simplify_tolerance: self.simplify_tolerance * factor,
+            curve_intrapolator_config: self.curve_intrapolator_config.scaled(factor),
+            filler_blank_boundary_pixels_tolerance: (self.filler_blank_boundary_pixels_tolerance
+                as f64
+                * factor)
+                .round() as usize,
+            symmetry_config: self
+                .symmetry_config
+                .map(|symmetry_config| symmetry_config.scaled(factor)),
+        }
     }
-}
 
-// Helper functions
-impl ShapeCompletor {
     fn get_test_paths(&self) -> Vec<PathI32> {
         let clusters = self.image.to_clusters(false);
 
@@ -182,7 +478,7 @@ impl ShapeCompletor {
             .collect()
     }
 
-    fn find_simplified_segments_from_paths(
+    fn find_segments_from_paths(
         &self,
         hole_rect: &BoundingRect,
         paths: Vec<PathI32>,
@@ -197,7 +493,7 @@ impl ShapeCompletor {
             .collect()
     }
 
-    /// Return a vector of *simplified* path segments whose heads are endpoints, pointing outwards from hole_rect.
+    /// Return a vector of *unsimplified* path segments whose heads are endpoints, pointing outwards from hole_rect.
     /// Segments are walked until 'max_num_points' is reached or another boundary point is reached, whichever happens first.
     fn find_segments_on_path_with_unique_endpoints(
         &self,
@@ -235,6 +531,31 @@ impl ShapeCompletor {
             .collect()
     }
 
+    /// Return the closed paths with no points on or next to the boundary of the hole.
+    fn find_paths_away_from_hole(hole_rect: &BoundingRect, paths: &[PathI32]) -> Vec<PathI32> {
+        paths
+            .iter()
+            .filter(|path| {
+                path.iter()
+                    .all(|&point| !hole_rect.have_point_on_boundary(point, 1))
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Each segment is walked once from each of its endpoints, so keep only the first walk of each segment.
+    fn remove_reversed_segments(path_segments: Vec<PathI32>) -> Vec<PathI32> {
+        let mut seen_ends = HashSet::new();
+        path_segments
+            .into_iter()
+            .filter(|segment| {
+                let (first, last) = (segment[0], segment[segment.len() - 1]);
+                // Check the reversed walk first, as a closed segment is its own reverse
+                !seen_ends.contains(&(last, first)) && seen_ends.insert((first, last))
+            })
+            .collect()
+    }
+
     /// The behavior is undefined unless path.len() == is_boundary_mask.len().
     fn walk_segment(
         &self,
@@ -281,80 +602,179 @@ impl ShapeCompletor {
             }
         }
 
-        // Simplify 'path_segment'
-        Ok(PathI32::from_points(visioniechor::reduce::reduce(
+        Ok(path_segment)
+    }
+
+    fn simplify_segment(path_segment: &PathI32, simplify_tolerance: f64) -> PathI32 {
+        PathI32::from_points(visioniechor::reduce::reduce(
             &path_segment.path,
-            self.simplify_tolerance,
-        )))
+            simplify_tolerance,
+        ))
     }
 
     /// The behavior is undefined unless 'path_segments' has an even number of elements.
     /// The behavior is also undefined unless every segment has at least 2 points.
     /// The behavior is also undefined unless all segments have their tails at index 0.
-    fn construct_match_item_set(&self, path_segments: &[PathI32]) -> Result<MatchItemSet, String> {
+    /// The behavior is also undefined unless 'raw_path_segments' are the unsimplified versions of 'path_segments'.
+    fn construct_match_item_set(
+        &self,
+        hole_rect: &BoundingRect,
+        path_segments: &[PathI32],
+        raw_path_segments: &[PathI32],
+        curve_intrapolator_config: CurveIntrapolatorConfig,
+    ) -> Result<MatchItemSet, String> {
         if path_segments.len() % 2 != 0 {
             return Err("There must be an even number of path segments.".into());
         }
 
-        let match_items_iter = path_segments.iter().map(|segment| {
-            assert!(segment.len() >= 2);
-            // 0 is tail
-            let direction = (segment[0] - segment[1]).to_point_f64().get_normalized();
-            MatchItem::new_with_default_id(segment[0].to_point_f64(), direction)
-        });
+        let tail_analyzer = TailAnalyzer::new(curve_intrapolator_config);
+        // The other endpoint of each pair is not known yet, so the hole size takes the place of the base length
+        let max_tangent_length = std::cmp::max(hole_rect.width(), hole_rect.height()) as f64;
+
+        let match_items_iter =
+            path_segments
+                .iter()
+                .zip(raw_path_segments.iter())
+                .map(|(segment, raw_segment)| {
+                    assert!(segment.len() >= 2);
+                    // 0 is tail, but the tail analyzer expects the tail at the end
+                    let mut path = segment.to_path_f64();
+                    path.path.reverse();
+                    let mut raw_path = raw_segment.to_path_f64();
+                    raw_path.path.reverse();
+                    let tail = tail_analyzer.analyze(path, Some(&raw_path), max_tangent_length);
+                    MatchItem::new_with_default_id(tail.point, tail.tangent)
+                        .with_confidence(tail.confidence)
+                });
         let mut match_item_set = MatchItemSet::new();
         match_items_iter.for_each(|match_item| match_item_set.push_and_set_id(match_item));
         Ok(match_item_set)
     }
 
-    /// Return true iff one of the matchings is successfully intrapolated
+    /// Return the intrapolated curves and their confidences of the first matching successfully intrapolated
     fn try_intrapolate_with_matchings(
         &self,
         hole_rect: BoundingRect,
         matchings: &[Matching],
         path_segments: &[PathI32],
+        raw_path_segments: &[PathI32],
+        curve_intrapolator_config: CurveIntrapolatorConfig,
         correct_tail_tangents: bool, // Not a configuration, but a fail-safe feature
-    ) -> Option<Vec<CompoundPath>> {
+    ) -> Option<(Vec<CompoundPath>, Vec<f64>)> {
+        let default_curve_model;
+        let curve_model = match &self.curve_model {
+            Some(curve_model) => curve_model.as_ref(),
+            None => {
+                default_curve_model = curve_intrapolator_config.curve_model();
+                default_curve_model.as_ref()
+            }
+        };
         let curve_intrapolator = CurveIntrapolator::new(
-            self.curve_intrapolator_config,
+            curve_intrapolator_config,
             hole_rect,
+            curve_model,
             self.debugger.as_ref(),
         );
 
-        'matching_loop: for matching in matchings.iter() {
-            let mut intrapolated_curves = vec![];
-            for &(index1, index2) in matching.iter() {
-                let (curve1, curve2) = (
-                    path_segments[index1].to_path_f64(),
-                    path_segments[index2].to_path_f64(),
-                );
-
-                if self.debugger.should_draw_simplified() {
-                    let color1 = Color::get_palette_color(1);
-                    let color2 = Color::get_palette_color(3);
-                    self.debugger.draw_path_f64(&color1, &curve1);
-                    self.debugger.draw_path_f64(&color2, &curve2);
-                }
+        // The curve of each matched pair of endpoints, shared by all matchings containing the pair in either order.
+        // It is keyed by the pair in ascending order, and runs from the endpoint of the smaller index.
+        // None if the pair cannot be bridged by a curve inside the hole.
+        let mut curve_cache: HashMap<(usize, usize), Option<IntrapolatedCurve>> = HashMap::new();
+        let cache_key = |(index1, index2): (usize, usize)| {
+            (std::cmp::min(index1, index2), std::cmp::max(index1, index2))
+        };
 
-                if let Some(intrapolated_curve) = curve_intrapolator
-                    .intrapolate_curve_between_curves(
-                        curve1,
-                        curve2,
-                        false,
-                        false,
+        'matching_loop: for matching in matchings.iter() {
+            for &index_pair in matching.iter() {
+                let key = cache_key(index_pair);
+                let intrapolated_curve = curve_cache.entry(key).or_insert_with(|| {
+                    self.intrapolate_endpoint_pair(
+                        &curve_intrapolator,
+                        key,
+                        path_segments,
+                        raw_path_segments,
                         correct_tail_tangents,
                     )
-                {
-                    intrapolated_curves.push(intrapolated_curve);
-                } else {
+                    // Check if the curve leaves the hole or crosses the visible contours around it
+                    .filter(|intrapolated_curve| {
+                        !self.does_curve_leave_hole(
+                            &hole_rect,
+                            &intrapolated_curve.curve,
+                            curve_intrapolator_config.hole_boundary_tolerance,
+                        )
+                    })
+                });
+                if intrapolated_curve.is_none() {
                     // A curve cannot be intrapolated, this matching is wrong
                     continue 'matching_loop;
                 }
             }
+
+            let intrapolated_curves: Vec<&CompoundPath> = matching
+                .iter()
+                .filter_map(|&index_pair| curve_cache[&cache_key(index_pair)].as_ref())
+                .map(|intrapolated_curve| &intrapolated_curve.curve)
+                .collect();
             // Check if any curves intersect with each other
-            if bezier_curves_intersection(&intrapolated_curves) {
+            if bezier_curves_intersection(
+                &intrapolated_curves,
+                curve_intrapolator_config.intersection_tolerance,
+            ) {
                 continue 'matching_loop;
             }
+            // Check if any curves are too close to each other to be filled apart,
+            // and refine them apart if enabled
+            let mut refined_curves = None;
+            if curve_intrapolator_config.min_curve_clearance > 0.0
+                && curves_closer_than(
+                    &intrapolated_curves,
+                    curve_intrapolator_config.min_curve_clearance,
+                    curve_intrapolator_config.min_curve_clearance * CLEARANCE_SAMPLING_RATIO,
+                )
+            {
+                refined_curves = match curve_intrapolator_config.refinement_config {
+                    Some(refinement_config) => Some(
+                        match self.refine_curves_apart(
+                            hole_rect,
+                            &intrapolated_curves,
+                            refinement_config,
+                            curve_intrapolator_config,
+                        ) {
+                            Some(refined_curves) => refined_curves,
+                            None => continue 'matching_loop,
+                        },
+                    ),
+                    None => continue 'matching_loop,
+                };
+            }
+
+            // Trust it to be the correct solution
+            let curve_confidences = matching
+                .iter()
+                .filter_map(|&index_pair| curve_cache[&cache_key(index_pair)].as_ref())
+                .map(|intrapolated_curve| intrapolated_curve.confidence)
+                .collect();
+            let intrapolated_curves: Vec<CompoundPath> = refined_curves
+                .unwrap_or_else(|| {
+                    matching
+                        .iter()
+                        .filter_map(|&index_pair| {
+                            curve_cache.remove(&cache_key(index_pair)).flatten()
+                        })
+                        .map(|intrapolated_curve| intrapolated_curve.curve)
+                        .collect()
+                })
+                .into_iter()
+                .zip(matching.iter())
+                // Each curve runs from the endpoint of the first index of its pair
+                .map(|(curve, &(index1, index2))| {
+                    if index1 > index2 {
+                        reverse_compound_path(&curve)
+                    } else {
+                        curve
+                    }
+                })
+                .collect();
 
             if self.debugger.should_draw_control_points() {
                 let color = Color::color(&ColorName::Black);
@@ -370,10 +790,574 @@ impl ShapeCompletor {
                 });
             }
 
-            // Trust it to be the correct solution
-            return Some(intrapolated_curves);
+            return Some((intrapolated_curves, curve_confidences));
         }
 
         None
     }
+
+    /// Refine the bezier curves of 'curves' one by one, keeping them 'min_curve_clearance' away from the others.
+    /// Return None if a curve cannot be refined, or the refined curves still intersect or are too close.
+    fn refine_curves_apart(
+        &self,
+        hole_rect: BoundingRect,
+        curves: &[&CompoundPath],
+        refinement_config: RefinementConfig,
+        curve_intrapolator_config: CurveIntrapolatorConfig,
+    ) -> Option<Vec<CompoundPath>> {
+        let refiner = CurveRefiner::new(
+            refinement_config,
+            hole_rect,
+            curve_intrapolator_config.hole_boundary_tolerance,
+        );
+        let clearance = curve_intrapolator_config.min_curve_clearance;
+
+        let mut refined_curves: Vec<CompoundPath> = vec![];
+        for (i, curve) in curves.iter().enumerate() {
+            let obstacles: Vec<&CompoundPath> = refined_curves
+                .iter()
+                .chain(curves.iter().skip(i + 1).copied())
+                .collect();
+            let mut refined_curve = CompoundPath::new();
+            for element in curve.iter() {
+                match element {
+                    CompoundPathElement::Spline(spline) => refined_curve
+                        .add_spline(refiner.refine_spline(spline, &obstacles, clearance)?),
+                    // Polylines have no control points to refine
+                    CompoundPathElement::PathF64(path) => refined_curve.add_path_f64(path.clone()),
+                    CompoundPathElement::PathI32(path) => refined_curve.add_path_i32(path.clone()),
+                }
+            }
+            refined_curves.push(refined_curve);
+        }
+
+        let refined_curve_refs: Vec<&CompoundPath> = refined_curves.iter().collect();
+        if bezier_curves_intersection(
+            &refined_curve_refs,
+            curve_intrapolator_config.intersection_tolerance,
+        ) || curves_closer_than(
+            &refined_curve_refs,
+            clearance,
+            clearance * CLEARANCE_SAMPLING_RATIO,
+        ) {
+            return None;
+        }
+        Some(refined_curves)
+    }
+
+    /// Intrapolate the curve between the endpoints of the path segments indexed by 'index_pair'.
+    fn intrapolate_endpoint_pair(
+        &self,
+        curve_intrapolator: &CurveIntrapolator,
+        (index1, index2): (usize, usize),
+        path_segments: &[PathI32],
+        raw_path_segments: &[PathI32],
+        correct_tail_tangents: bool,
+    ) -> Option<IntrapolatedCurve> {
+        let (curve1, curve2) = (
+            path_segments[index1].to_path_f64(),
+            path_segments[index2].to_path_f64(),
+        );
+
+        if self.debugger.should_draw_simplified() {
+            let color1 = Color::get_palette_color(1);
+            let color2 = Color::get_palette_color(3);
+            self.debugger.draw_path_f64(&color1, &curve1);
+            self.debugger.draw_path_f64(&color2, &curve2);
+        }
+
+        curve_intrapolator.intrapolate_curve_between_curves(
+            curve1,
+            curve2,
+            Some((
+                raw_path_segments[index1].to_path_f64(),
+                raw_path_segments[index2].to_path_f64(),
+            )),
+            false,
+            false,
+            correct_tail_tangents,
+        )
+    }
+
+    /// Return true iff a point of 'curve' is farther than 'tolerance' outside 'hole_rect',
+    /// or a point of 'curve' outside 'hole_rect' lies on a visible contour pixel farther than 'tolerance'
+    /// from both endpoints of 'curve', i.e. the curve crosses an existing stroke.
+    fn does_curve_leave_hole(
+        &self,
+        hole_rect: &BoundingRect,
+        curve: &CompoundPath,
+        tolerance: f64,
+    ) -> bool {
+        let points = sample_compound_path(curve, 0.5);
+        let (first_point, last_point) = match (points.first(), points.last()) {
+            (Some(&first_point), Some(&last_point)) => (first_point, last_point),
+            _ => return false,
+        };
+
+        points.iter().any(|&point| {
+            let outside_distance = f64::max(
+                f64::max(
+                    hole_rect.left as f64 - point.x,
+                    point.x - hole_rect.right as f64,
+                ),
+                f64::max(
+                    hole_rect.top as f64 - point.y,
+                    point.y - hole_rect.bottom as f64,
+                ),
+            );
+            if outside_distance <= 0.0 {
+                false
+            } else if outside_distance > tolerance {
+                true
+            } else {
+                point.distance_to(first_point) > tolerance
+                    && point.distance_to(last_point) > tolerance
+                    && self.is_visible_contour_pixel(
+                        hole_rect,
+                        PointI32::new(point.x.floor() as i32, point.y.floor() as i32),
+                    )
+            }
+        })
+    }
+
+    /// A visible contour pixel is a foreground pixel outside the hole with a background 4-neighbor outside the hole.
+    /// Neighbors outside the image are background.
+    fn is_visible_contour_pixel(&self, hole_rect: &BoundingRect, point: PointI32) -> bool {
+        let is_in_hole = |point: PointI32| {
+            hole_rect.left <= point.x
+                && point.x < hole_rect.right
+                && hole_rect.top <= point.y
+                && point.y < hole_rect.bottom
+        };
+        if is_in_hole(point) || !self.image.get_pixel_safe(point.x, point.y) {
+            return false;
+        }
+        [
+            PointI32::new(1, 0),
+            PointI32::new(-1, 0),
+            PointI32::new(0, 1),
+            PointI32::new(0, -1),
+        ]
+        .iter()
+        .map(|&offset| point + offset)
+        .any(|neighbor| !is_in_hole(neighbor) && !self.image.get_pixel_safe(neighbor.x, neighbor.y))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filler::FilledHoleElement;
+    use visioniechor::{PointF64, Spline};
+
+    /// A compound path of the single cubic bezier curve with 'points' as control points
+    fn cubic_curve(points: [(f64, f64); 4]) -> CompoundPath {
+        let [p0, p1, p2, p3] = points.map(|(x, y)| PointF64::new(x, y));
+        let mut spline = Spline::new(p0);
+        spline.add(p1, p2, p3);
+        let mut compound_path = CompoundPath::new();
+        compound_path.add_spline(spline);
+        compound_path
+    }
+
+    #[test]
+    fn refine_curves_apart_restores_clearance() {
+        // GIVEN
+        // Two curves across a hole bulging towards each other, 2 pixels apart at their closest
+        let hole_rect = BoundingRect::new_x_y_w_h(0, 0, 40, 40);
+        let curves = [
+            cubic_curve([(0.0, 10.0), (10.0, 22.0), (30.0, 22.0), (40.0, 10.0)]),
+            cubic_curve([(0.0, 30.0), (10.0, 18.0), (30.0, 18.0), (40.0, 30.0)]),
+        ];
+        let curves = [&curves[0], &curves[1]];
+        let curve_intrapolator_config = CurveIntrapolatorConfig {
+            min_curve_clearance: 6.0,
+            ..Default::default()
+        };
+        let completor = ShapeCompletor::new(
+            BinaryImage::new_w_h(40, 40),
+            1.0,
+            curve_intrapolator_config,
+            0,
+            None,
+        );
+        assert!(curves_closer_than(&curves, 6.0, 1.5));
+
+        // WHEN
+        let refined_curves = completor
+            .refine_curves_apart(
+                hole_rect,
+                &curves,
+                RefinementConfig::default(),
+                curve_intrapolator_config,
+            )
+            .unwrap();
+
+        // THEN
+        let refined_curves: Vec<&CompoundPath> = refined_curves.iter().collect();
+        assert!(!curves_closer_than(&refined_curves, 6.0, 1.5));
+        for (curve, refined_curve) in curves.iter().zip(refined_curves.iter()) {
+            let (points, refined_points) = (
+                sample_compound_path(curve, 1.0),
+                sample_compound_path(refined_curve, 1.0),
+            );
+            assert_eq!(points.first(), refined_points.first());
+            assert_eq!(points.last(), refined_points.last());
+        }
+    }
+
+    #[test]
+    fn curves_leaving_hole_or_crossing_contours_are_rejected() {
+        // GIVEN
+        // A hole with a vertical line of foreground pixels along its right side, just outside of it
+        let hole_rect = BoundingRect::new_x_y_w_h(20, 20, 40, 40);
+        let mut image = BinaryImage::new_w_h(100, 100);
+        for y in 0..100 {
+            image.set_pixel(60, y, true);
+        }
+        let completor = ShapeCompletor::new(image, 1.0, Default::default(), 0, None);
+        let completor_without_line = ShapeCompletor::new(
+            BinaryImage::new_w_h(100, 100),
+            1.0,
+            Default::default(),
+            0,
+            None,
+        );
+        // Bulges 10 pixels out of the left side of the hole
+        let bulging_curve = cubic_curve([(20.0, 30.0), (7.0, 33.0), (7.0, 47.0), (20.0, 50.0)]);
+        // Bulges 0.5 pixels out of the right side of the hole, within the tolerance, onto the line
+        let contour_crossing_curve =
+            cubic_curve([(50.0, 25.0), (64.0, 28.0), (64.0, 52.0), (50.0, 55.0)]);
+        let inside_curve = cubic_curve([(25.0, 30.0), (45.0, 33.0), (45.0, 47.0), (25.0, 50.0)]);
+
+        // WHEN
+        let leaves_hole = |completor: &ShapeCompletor, curve| {
+            completor.does_curve_leave_hole(&hole_rect, curve, 1.0)
+        };
+
+        // THEN
+        assert!(leaves_hole(&completor, &bulging_curve));
+        assert!(leaves_hole(&completor, &contour_crossing_curve));
+        assert!(!leaves_hole(
+            &completor_without_line,
+            &contour_crossing_curve
+        ));
+        assert!(!leaves_hole(&completor, &inside_curve));
+    }
+
+    #[test]
+    fn coverage_without_supersampling_matches_complete_shape() {
+        // GIVEN
+        // A disk with a hole across its right side
+        let mut image = BinaryImage::new_w_h(100, 100);
+        let hole_rect = BoundingRect::new_x_y_w_h(65, 35, 25, 30);
+        for y in 0..100 {
+            for x in 0..100 {
+                let is_in_disk =
+                    ((x as f64 - 50.0).powi(2) + (y as f64 - 50.0).powi(2)).sqrt() < 30.0;
+                let is_in_hole = (65..90).contains(&x) && (35..65).contains(&y);
+                image.set_pixel(x, y, is_in_disk && !is_in_hole);
+            }
+        }
+        let completor = ShapeCompletor::new(image, 2.0, Default::default(), 3, None);
+
+        // WHEN
+        let filled_hole = completor.complete_shape(hole_rect).unwrap();
+        let coverage = completor
+            .complete_shape_with_coverage(hole_rect, 1)
+            .unwrap();
+
+        // THEN
+        assert_eq!(
+            (coverage.width, coverage.height),
+            (filled_hole.width, filled_hole.height)
+        );
+        for i in 0..filled_hole.height {
+            for j in 0..filled_hole.width {
+                match filled_hole[i][j] {
+                    FilledHoleElement::Blank => assert_eq!(coverage[i][j], 0.0),
+                    FilledHoleElement::Structure => {
+                        assert!((0.0..=1.0).contains(&coverage[i][j]))
+                    }
+                    FilledHoleElement::Texture => assert_eq!(coverage[i][j], 1.0),
+                }
+            }
+        }
+        assert!(filled_hole.elems.contains(&FilledHoleElement::Texture));
+        assert!(filled_hole.elems.contains(&FilledHoleElement::Blank));
+    }
+
+    /// Run the stages of 'complete_shape' up to curve intrapolation.
+    /// Return the parameter scaling factor and the control points of each intrapolated curve.
+    fn intrapolate_curves(
+        completor: &ShapeCompletor,
+        hole_rect: BoundingRect,
+    ) -> (f64, Vec<Vec<PointF64>>) {
+        let raw_path_segments =
+            completor.find_segments_from_paths(&hole_rect, completor.get_test_paths());
+        let factor = completor
+            .parameter_scale
+            .factor(&hole_rect, &raw_path_segments);
+        let parameters = completor.calculate_hole_parameters(&hole_rect, &raw_path_segments);
+        let path_segments: Vec<PathI32> = raw_path_segments
+            .iter()
+            .map(|segment| ShapeCompletor::simplify_segment(segment, parameters.simplify_tolerance))
+            .collect();
+        let match_item_set = completor
+            .construct_match_item_set(
+                &hole_rect,
+                &path_segments,
+                &raw_path_segments,
+                parameters.curve_intrapolator_config,
+            )
+            .unwrap();
+        let matchings = completor
+            .matching_strategy
+            .find_matchings(match_item_set)
+            .unwrap();
+        let (curves, _) = completor
+            .try_intrapolate_with_matchings(
+                hole_rect,
+                &matchings,
+                &path_segments,
+                &raw_path_segments,
+                parameters.curve_intrapolator_config,
+                false,
+            )
+            .unwrap();
+        let control_points = curves
+            .iter()
+            .map(|curve| {
+                curve
+                    .iter()
+                    .flat_map(|element| match element {
+                        CompoundPathElement::Spline(spline) => spline.points.clone(),
+                        CompoundPathElement::PathF64(path) => path.path.clone(),
+                        CompoundPathElement::PathI32(path) => path.to_path_f64().path,
+                    })
+                    .collect()
+            })
+            .collect();
+        (factor, control_points)
+    }
+
+    #[test]
+    fn relative_parameter_scale_scales_curves_with_image() {
+        // GIVEN
+        // A disk with a hole across its right side, and the same image and hole upscaled 2 times
+        let image_and_hole = |scale: usize| {
+            let size = 100 * scale;
+            let mut image = BinaryImage::new_w_h(size, size);
+            for y in 0..size {
+                for x in 0..size {
+                    let (x1, y1) = (x / scale, y / scale);
+                    let is_in_disk =
+                        ((x1 as f64 - 50.0).powi(2) + (y1 as f64 - 50.0).powi(2)).sqrt() < 30.0;
+                    let is_in_hole = (65..90).contains(&x1) && (35..65).contains(&y1);
+                    image.set_pixel(x, y, is_in_disk && !is_in_hole);
+                }
+            }
+            let scale = scale as i32;
+            (
+                image,
+                BoundingRect::new_x_y_w_h(65 * scale, 35 * scale, 25 * scale, 30 * scale),
+            )
+        };
+        let completor = |image| {
+            ShapeCompletor::new(image, 2.0, Default::default(), 3, None).with_parameter_scale(
+                ParameterScale::Relative {
+                    reference_hole_size: 30.0,
+                    reference_segment_length: 40.0,
+                },
+            )
+        };
+        let (image1, hole_rect1) = image_and_hole(1);
+        let (image2, hole_rect2) = image_and_hole(2);
+
+        // WHEN
+        let (factor1, curves1) = intrapolate_curves(&completor(image1), hole_rect1);
+        let (factor2, curves2) = intrapolate_curves(&completor(image2), hole_rect2);
+
+        // THEN
+        assert!((factor2 - 2.0 * factor1).abs() < 1e-9);
+        assert!(!curves1.is_empty());
+        assert_eq!(curves1.len(), curves2.len());
+        for (points1, points2) in curves1.iter().zip(curves2.iter()) {
+            assert_eq!(points1.len(), points2.len());
+            for (&point1, &point2) in points1.iter().zip(points2.iter()) {
+                assert!((point1 * 2.0).distance_to(point2) < 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn empty_hole_is_completed_with_full_confidence() {
+        // GIVEN
+        // A square far away from the hole
+        let mut image = BinaryImage::new_w_h(100, 100);
+        for y in 10..20 {
+            for x in 10..20 {
+                image.set_pixel(x, y, true);
+            }
+        }
+        let hole_rect = BoundingRect::new_x_y_w_h(60, 60, 20, 20);
+        let completor = ShapeCompletor::new(image, 2.0, Default::default(), 3, None);
+
+        // WHEN
+        let result = completor.complete_shape_with_report(hole_rect).unwrap();
+
+        // THEN
+        assert!(result.curve_confidences.is_empty());
+        assert_eq!(result.confidence, 1.0);
+        assert!(result
+            .filled_hole
+            .elems
+            .iter()
+            .all(|&element| element == FilledHoleElement::Blank));
+    }
+
+    #[test]
+    fn coverage_of_hole_completed_by_symmetry_is_not_anti_aliased() {
+        // GIVEN
+        // A triangle mirrored about x = 30, with a hole on its right side
+        let is_triangle = |x: usize, y: usize| {
+            (10..50).contains(&y) && (x as f64 + 0.5 - 30.0).abs() < (y - 10) as f64 / 2.0
+        };
+        let hole_rect = BoundingRect::new_x_y_w_h(38, 30, 8, 10);
+        let mut image = BinaryImage::new_w_h(60, 60);
+        for y in 0..60 {
+            for x in 0..60 {
+                let is_in_hole = (38..46).contains(&x) && (30..40).contains(&y);
+                image.set_pixel(x, y, !is_in_hole && is_triangle(x, y));
+            }
+        }
+        let completor = ShapeCompletor::new(image, 2.0, Default::default(), 3, None)
+            .with_symmetry_config(SymmetryConfig::default());
+
+        // WHEN
+        let result = completor.complete_shape_with_report(hole_rect).unwrap();
+        let coverage = completor
+            .complete_shape_with_coverage(hole_rect, 4)
+            .unwrap();
+
+        // THEN
+        assert!(result.reflection_axis.is_some());
+        assert!(result
+            .filled_hole
+            .elems
+            .contains(&FilledHoleElement::Structure));
+        for i in 0..coverage.height {
+            for j in 0..coverage.width {
+                let (x, y) = (hole_rect.left as usize + j, hole_rect.top as usize + i);
+                let expected = if is_triangle(x, y) { 1.0 } else { 0.0 };
+                assert_eq!(coverage[i][j], expected);
+            }
+        }
+    }
+
+    #[test]
+    fn complete_shape_into_image_writes_completion_into_copy() {
+        // GIVEN
+        // A disk with a hole across its right side
+        let mut image = BinaryImage::new_w_h(100, 100);
+        let hole_rect = BoundingRect::new_x_y_w_h(65, 35, 25, 30);
+        for y in 0..100 {
+            for x in 0..100 {
+                let is_in_disk =
+                    ((x as f64 - 50.0).powi(2) + (y as f64 - 50.0).powi(2)).sqrt() < 30.0;
+                let is_in_hole = (65..90).contains(&x) && (35..65).contains(&y);
+                image.set_pixel(x, y, is_in_disk && !is_in_hole);
+            }
+        }
+        let completor = ShapeCompletor::new(image.clone(), 2.0, Default::default(), 3, None);
+        let apply_config = ApplyConfig::default();
+
+        // WHEN
+        let filled_hole = completor.complete_shape(hole_rect).unwrap();
+        let completed_image = completor
+            .complete_shape_into_image(hole_rect, &apply_config)
+            .unwrap();
+
+        // THEN
+        for y in 0..100 {
+            for x in 0..100 {
+                let (i, j) = (y as i32 - hole_rect.top, x as i32 - hole_rect.left);
+                let expected = if (0..hole_rect.height()).contains(&i)
+                    && (0..hole_rect.width()).contains(&j)
+                {
+                    filled_hole[i as usize][j as usize] != FilledHoleElement::Blank
+                } else {
+                    image.get_pixel(x, y)
+                };
+                assert_eq!(completed_image.get_pixel(x, y), expected);
+                assert_eq!(completor.image.get_pixel(x, y), image.get_pixel(x, y));
+            }
+        }
+    }
+
+    #[test]
+    fn remove_reversed_segments_keeps_closed_segments() {
+        // GIVEN
+        let path_i32 = |points: &[(i32, i32)]| {
+            PathI32::from_points(points.iter().map(|&(x, y)| PointI32::new(x, y)).collect())
+        };
+        let segments = vec![
+            path_i32(&[(0, 0), (5, 0), (5, 5)]),
+            path_i32(&[(5, 5), (5, 0), (0, 0)]),
+            path_i32(&[(9, 9), (12, 9), (12, 12), (9, 9)]),
+        ];
+
+        // WHEN
+        let segments = ShapeCompletor::remove_reversed_segments(segments);
+
+        // THEN
+        assert_eq!(segments.len(), 2);
+        assert_eq!(segments[0][0], PointI32::new(0, 0));
+        assert_eq!(segments[1][0], PointI32::new(9, 9));
+    }
+
+    #[test]
+    fn match_items_take_smoothed_directions_of_jittered_tails() {
+        // GIVEN
+        // Two straight strokes along the x-axis with alternating jitter of 1 pixel, whose tails (at index 0)
+        // face each other across a hole
+        let hole_rect = BoundingRect::new_x_y_w_h(40, -20, 20, 40);
+        let jittered_stroke = |tail_x: i32, step: i32| {
+            PathI32::from_points(
+                (0..=12)
+                    .map(|i| PointI32::new(tail_x + step * i, i % 2))
+                    .collect(),
+            )
+        };
+        let path_segments = vec![jittered_stroke(40, -4), jittered_stroke(60, 4)];
+        let completor = ShapeCompletor::new(
+            BinaryImage::new_w_h(100, 100),
+            1.0,
+            Default::default(),
+            0,
+            None,
+        );
+
+        // WHEN
+        let match_items = completor
+            .construct_match_item_set(
+                &hole_rect,
+                &path_segments,
+                &path_segments,
+                Default::default(),
+            )
+            .unwrap();
+
+        // THEN
+        // The direction of the first two points of each stroke is off by about 0.245 radians
+        let true_directions = [PointF64::new(1.0, 0.0), PointF64::new(-1.0, 0.0)];
+        for (match_item, true_direction) in match_items.iter().zip(true_directions.iter()) {
+            let angle = match_item
+                .direction
+                .dot(*true_direction)
+                .clamp(-1.0, 1.0)
+                .acos();
+            assert!(angle < 0.15);
+        }
+    }
 }
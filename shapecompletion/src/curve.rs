@@ -1,45 +1,176 @@
-use visioniechor::{BoundingRect, Color, CompoundPath, PathF64, PointF64, Spline};
+use visioniechor::{BoundingRect, Color, CompoundPath, PathF64, PointF64};
 
 use crate::{
-    debugger::Debugger,
-    geo::{
-        calculate_intersection, calculate_midpoint, calculate_unit_normal_of_line,
-        find_corners_open_path, find_new_point_from_4_point_scheme, retract_point,
-        LineIntersectionResult,
+    curve_model::{
+        BezierCurveModel, BiarcCurveModel, ClothoidCurveModel, CurvatureContinuousCurveModel,
+        CurveModel, Gap,
     },
+    debugger::Debugger,
+    geo::{calculate_intersection, calculate_unit_normal_of_line, LineIntersectionResult},
+    refinement::RefinementConfig,
+    tail::{SmoothingScheme, Tail, TailAnalyzer, TailModel},
 };
 
+/// The built-in curve model used to bridge the gap between two endpoints
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CurveMode {
+    /// See 'BezierCurveModel'
+    Bezier,
+    /// See 'ClothoidCurveModel'
+    Clothoid,
+    /// See 'BiarcCurveModel'
+    Biarc,
+    /// See 'CurvatureContinuousCurveModel'
+    CurvatureContinuous,
+}
+
 #[derive(Clone, Copy)]
 pub struct CurveIntrapolatorConfig {
     // Smoothing
+    pub smoothing_scheme: SmoothingScheme,
+    /// Only used by 'SmoothingScheme::FourPoint'
     pub outset_ratio: f64,
     pub min_segment_length: f64,
     pub smooth_max_iterations: usize,
     pub corner_threshold: f64,
+    /// Length in pixels of the support on each side of a point, over which its angle is measured for corner detection.
+    /// Corners are detected by the angles between adjacent segments if it is 0.0.
+    pub corner_scale: f64,
     // Tail tangent approx.
     pub tail_tangent_num_points: usize, // [2, Inf]
     pub tail_weight_multiplier: f64,
     pub control_points_retract_ratio: f64,
+    // Tail model
+    pub tail_model: TailModel,
+    /// Maximum number of raw points at the tail fitted by the least-squares tail model
+    pub least_squares_num_points: usize,
+    /// Degree of the polynomials fitted by the least-squares tail model, at least 1
+    pub least_squares_degree: usize,
+    // Curve intrapolation
+    pub curve_mode: CurveMode,
+    /// Maximum angle in radians between two lines for them to be considered parallel
+    pub parallel_tolerance: f64,
+    /// Maximum number of bezier curves bridging a single S-shaped gap
+    pub max_gap_pieces: usize,
+    /// If not None, the control points of bezier curves are placed by minimizing the bending energy
+    /// instead of retracting them into the hole, and curves too close to each other are refined apart
+    pub refinement_config: Option<RefinementConfig>,
+    /// If true, clothoids are output as cubic bezier curves, otherwise as polylines
+    pub clothoid_as_bezier: bool,
+    /// Maximum difference in radians between the angles made by the two tangents with the base line,
+    /// and between each tail curvature and the arc curvature over the base line, for a biarc to be reduced
+    /// to a single arc
+    pub biarc_single_arc_tolerance: f64,
+    // Endpoint confidence
+    pub confidence_reference_length: f64,
+    // Corner preservation
+    /// If true, two straight tails whose extensions meet inside the hole are connected through the meeting point
+    pub preserve_corners: bool,
+    /// Maximum distance of a point from the line for a tail to be considered straight
+    pub straight_tail_tolerance: f64,
+    /// Minimum length of a straight tail
+    pub straight_tail_min_length: f64,
+    // Curve confidence
+    /// Factor applied to the confidence of curves intrapolated with corrected tail tangents
+    pub corrected_tangents_confidence_factor: f64,
+    // Curve validation
+    /// Maximum distance by which an intrapolated curve may leave the hole.
+    /// Outside the hole, a curve may not touch any visible contour pixel farther than this distance from its endpoints.
+    pub hole_boundary_tolerance: f64,
+    /// Accuracy in pixels of the intersection test between intrapolated curves
+    pub intersection_tolerance: f64,
+    /// Minimum distance in pixels between any two intrapolated curves, except where their endpoints are already closer.
+    /// 0.0 disables the constraint.
+    pub min_curve_clearance: f64,
 }
 
 impl Default for CurveIntrapolatorConfig {
     fn default() -> Self {
         Self {
+            smoothing_scheme: SmoothingScheme::FourPoint,
             outset_ratio: 8.0,
             min_segment_length: 4.0,
             smooth_max_iterations: 2,
             corner_threshold: std::f64::consts::FRAC_PI_2,
+            corner_scale: 0.0,
             tail_tangent_num_points: 5,
             tail_weight_multiplier: 1.5,
             control_points_retract_ratio: 0.4,
+            tail_model: TailModel::Subdivision,
+            least_squares_num_points: 16,
+            least_squares_degree: 2,
+            curve_mode: CurveMode::Bezier,
+            parallel_tolerance: 1e-2,
+            max_gap_pieces: 4,
+            refinement_config: None,
+            clothoid_as_bezier: true,
+            biarc_single_arc_tolerance: 0.05,
+            confidence_reference_length: 16.0,
+            preserve_corners: false,
+            straight_tail_tolerance: 1.0,
+            straight_tail_min_length: 8.0,
+            corrected_tangents_confidence_factor: 0.5,
+            hole_boundary_tolerance: 1.0,
+            intersection_tolerance: 0.25,
+            min_curve_clearance: 0.0,
+        }
+    }
+}
+
+impl CurveIntrapolatorConfig {
+    /// Return the config with all parameters in pixels (or numbers of pixels) multiplied by 'factor'.
+    /// 'tail_tangent_num_points' counts simplified points, whose number is kept when the simplify tolerance
+    /// is scaled by the same factor, so it is not scaled.
+    pub fn scaled(&self, factor: f64) -> Self {
+        Self {
+            min_segment_length: self.min_segment_length * factor,
+            corner_scale: self.corner_scale * factor,
+            least_squares_num_points: std::cmp::max(
+                (self.least_squares_num_points as f64 * factor).round() as usize,
+                self.least_squares_degree + 2,
+            ),
+            confidence_reference_length: self.confidence_reference_length * factor,
+            straight_tail_tolerance: self.straight_tail_tolerance * factor,
+            straight_tail_min_length: self.straight_tail_min_length * factor,
+            hole_boundary_tolerance: self.hole_boundary_tolerance * factor,
+            intersection_tolerance: self.intersection_tolerance * factor,
+            min_curve_clearance: self.min_curve_clearance * factor,
+            ..*self
+        }
+    }
+
+    /// The built-in curve model selected by 'curve_mode'
+    pub fn curve_model(&self) -> Box<dyn CurveModel> {
+        match self.curve_mode {
+            CurveMode::Bezier => Box::new(BezierCurveModel {
+                control_points_retract_ratio: self.control_points_retract_ratio,
+                max_pieces: self.max_gap_pieces,
+                parallel_tolerance: self.parallel_tolerance,
+                refinement_config: self.refinement_config,
+            }),
+            CurveMode::Clothoid => Box::new(ClothoidCurveModel {
+                as_bezier: self.clothoid_as_bezier,
+            }),
+            CurveMode::Biarc => Box::new(BiarcCurveModel {
+                single_arc_tolerance: self.biarc_single_arc_tolerance,
+            }),
+            CurveMode::CurvatureContinuous => Box::new(CurvatureContinuousCurveModel),
         }
     }
 }
 
+/// A curve intrapolated between two endpoints, and how trustworthy it is
+pub struct IntrapolatedCurve {
+    pub curve: CompoundPath,
+    /// In [0.0, 1.0]
+    pub confidence: f64,
+}
+
 /// intrapolate in-between curve given 2 curves
 pub struct CurveIntrapolator<'a> {
     pub config: CurveIntrapolatorConfig,
     pub hole_rect: BoundingRect,
+    pub curve_model: &'a dyn CurveModel,
     pub debugger: &'a dyn Debugger,
 }
 
@@ -48,11 +179,13 @@ impl<'a> CurveIntrapolator<'a> {
     pub fn new(
         config: CurveIntrapolatorConfig,
         hole_rect: BoundingRect,
+        curve_model: &'a dyn CurveModel,
         debugger: &'a dyn Debugger,
     ) -> Self {
         Self {
             config,
             hole_rect,
+            curve_model,
             debugger,
         }
     }
@@ -61,14 +194,20 @@ impl<'a> CurveIntrapolator<'a> {
     /// The endpoints of the intrapolated curve are defined by 'at_tail_curve1' and 'at_tail_curve2'.
     /// If 'at_tail_curve1' is true, the last point of 'curve1' is used as one of the endpoints of the curve, otherwise the first
     /// point (head) of 'curve1' is used. The same goes for 'at_tail_curve2' and 'curve2'.
+    /// 'raw_curves', if any, are the unsimplified versions of 'curve1' and 'curve2', in the same directions.
+    /// The confidence of the returned curve is the product of 4 factors, each in [0.0, 1.0]:
+    /// the agreement of the tail tangents with the base line, the change in curvature across the gap
+    /// relative to the base length, the base length relative to the hole size,
+    /// and 'corrected_tangents_confidence_factor' if 'correct_tail_tangents' is true.
     pub fn intrapolate_curve_between_curves(
         &self,
         mut curve1: PathF64,
         mut curve2: PathF64,
+        raw_curves: Option<(PathF64, PathF64)>,
         at_tail_curve1: bool,
         at_tail_curve2: bool,
         correct_tail_tangents: bool,
-    ) -> Option<CompoundPath> {
+    ) -> Option<IntrapolatedCurve> {
         let color1 = Color::get_palette_color(1);
         let color2 = Color::get_palette_color(3);
 
@@ -80,204 +219,136 @@ impl<'a> CurveIntrapolator<'a> {
             curve2.path.reverse();
         }
         let (curve1, curve2) = (curve1, curve2);
+        let raw_curves = raw_curves.map(|(mut raw_curve1, mut raw_curve2)| {
+            if !at_tail_curve1 {
+                raw_curve1.path.reverse();
+            }
+            if !at_tail_curve2 {
+                raw_curve2.path.reverse();
+            }
+            (raw_curve1, raw_curve2)
+        });
+        let (raw_curve1, raw_curve2) = match &raw_curves {
+            Some((raw_curve1, raw_curve2)) => (Some(raw_curve1), Some(raw_curve2)),
+            None => (None, None),
+        };
 
         let (endpoint1, endpoint2) = (curve1[curve1.len() - 1], curve2[curve2.len() - 1]);
         let base_length = endpoint1.distance_to(endpoint2);
 
-        //# Curve smoothing
-        let outset_ratio = self.config.outset_ratio;
-        let min_segment_length = self.config.min_segment_length;
-        let max_iterations = self.config.smooth_max_iterations;
-        let corner_threshold = self.config.corner_threshold;
-
-        let (smooth_curve1, corners1) = Self::smooth_open_curve_iterative(
-            curve1,
-            outset_ratio,
-            min_segment_length,
-            max_iterations,
-            corner_threshold,
-        );
-        let (smooth_curve2, corners2) = Self::smooth_open_curve_iterative(
-            curve2,
-            outset_ratio,
-            min_segment_length,
-            max_iterations,
-            corner_threshold,
-        );
+        //# Curve smoothing and tail tangent approximation
+        let tail_analyzer = TailAnalyzer::new(self.config);
+        let mut tail1 = tail_analyzer.analyze(curve1, raw_curve1, base_length);
+        let mut tail2 = tail_analyzer.analyze(curve2, raw_curve2, base_length);
 
         if self.debugger.should_draw_smoothed() {
-            self.debugger.draw_path_f64(&color1, &smooth_curve1);
-            self.debugger.draw_path_f64(&color2, &smooth_curve2);
+            self.debugger.draw_path_f64(&color1, &tail1.smoothed_path);
+            self.debugger.draw_path_f64(&color2, &tail2.smoothed_path);
         }
 
-        //# Tail tangent approximation
-        let tail_tangent_n_points = self.config.tail_tangent_num_points;
-        let tail_weight_multiplier = self.config.tail_weight_multiplier;
-        let (smooth_curve1_len, smooth_curve2_len) = (smooth_curve1.len(), smooth_curve2.len());
-        let tail_tangent1 = Self::calculate_weighted_average_tangent_at_tail(
-            smooth_curve1,
-            &corners1,
-            std::cmp::min(tail_tangent_n_points, smooth_curve1_len),
-            base_length,
-            tail_weight_multiplier,
-        );
-        let tail_tangent2 = Self::calculate_weighted_average_tangent_at_tail(
-            smooth_curve2,
-            &corners2,
-            std::cmp::min(tail_tangent_n_points, smooth_curve2_len),
-            base_length,
-            tail_weight_multiplier,
-        );
-
-        let (tail_tangent1, tail_tangent2) = if correct_tail_tangents {
-            Self::correct_tail_tangents(endpoint1, tail_tangent1, endpoint2, tail_tangent2)
-        } else {
-            (tail_tangent1, tail_tangent2)
-        };
+        if correct_tail_tangents {
+            let (tail_tangent1, tail_tangent2) =
+                Self::correct_tail_tangents(endpoint1, tail1.tangent, endpoint2, tail2.tangent);
+            tail1.tangent = tail_tangent1;
+            tail2.tangent = tail_tangent2;
+        }
 
         if self.debugger.should_draw_tail_tangents() {
             let tangent_visual_length =
                 (self.hole_rect.width() + self.hole_rect.height()) as f64 / 3.5;
-            let to_point1 = endpoint1 + tail_tangent1.get_normalized() * tangent_visual_length;
-            let to_point2 = endpoint2 + tail_tangent2.get_normalized() * tangent_visual_length;
+            let to_point1 = endpoint1 + tail1.tangent.get_normalized() * tangent_visual_length;
+            let to_point2 = endpoint2 + tail2.tangent.get_normalized() * tangent_visual_length;
             self.debugger.draw_line_f64(&color1, endpoint1, to_point1);
             self.debugger.draw_line_f64(&color2, endpoint2, to_point2);
         }
 
+        //# Curve confidence
+        let confidence = self.calculate_curve_confidence(&tail1, &tail2, correct_tail_tangents);
+
+        //# Corner preservation
+        if self.config.preserve_corners {
+            if let Some(polyline) = self.calculate_sharp_corner(&tail1, &tail2) {
+                return Some(IntrapolatedCurve {
+                    curve: polyline,
+                    confidence,
+                });
+            }
+        }
+
         //# Curve intrapolation
-        self.calculate_whole_curve(endpoint1, tail_tangent1, endpoint2, tail_tangent2)
+        self.curve_model
+            .intrapolate(&Gap {
+                from: tail1,
+                to: tail2,
+                hole_rect: self.hole_rect,
+                hole_boundary_tolerance: self.config.hole_boundary_tolerance,
+            })
+            .map(|curve| IntrapolatedCurve { curve, confidence })
     }
 }
 
 // Helper functions
 impl<'a> CurveIntrapolator<'a> {
-    /// Apply the 4-point scheme subdivision on 'path' in a convolutional manner iteratively, preserving corners.
-    /// The corners of the smoothed path are returned as a bool mask.
-    /// Segments (at any point during iteration) shorter than 'min_segment_length' are not further subdivided.
-    /// If no subdivision is performed, the iterative process is terminated early.
-    /// 'path' is returned as-is if path.len() < 4
-    fn smooth_open_curve_iterative(
-        mut path: PathF64,
-        outset_ratio: f64,
-        min_segment_length: f64,
-        max_iterations: usize,
-        corner_threshold: f64,
-    ) -> (PathF64, Vec<bool>) {
-        let mut corners = find_corners_open_path(&path, corner_threshold);
-
-        if path.len() < 4 {
-            return (path, corners);
-        }
-
-        for _ in 0..max_iterations {
-            let can_terminate_early = Self::smooth_open_curve_step(
-                &mut path,
-                &mut corners,
-                outset_ratio,
-                min_segment_length,
-            );
-
-            // Early termination
-            if can_terminate_early {
-                break;
-            }
+    fn calculate_curve_confidence(
+        &self,
+        tail1: &Tail,
+        tail2: &Tail,
+        correct_tail_tangents: bool,
+    ) -> f64 {
+        let base = tail2.point - tail1.point;
+        let base_length = base.norm();
+        if base_length < f64::EPSILON {
+            return 0.0;
         }
+        let base_direction = base / base_length;
 
-        (path, corners)
-    }
-
-    /// Return true if no subdivision is done in this step.
-    fn smooth_open_curve_step(
-        path: &mut PathF64,
-        corners: &mut Vec<bool>,
-        outset_ratio: f64,
-        min_segment_length: f64,
-    ) -> bool {
-        let mut new_points = vec![path[0]];
-        let mut new_corners = vec![corners[0]];
-
-        // Duplicate the last point to make sure all segments except the first are subdivided
-        path.add(path[path.len() - 1]);
-
-        // Apply 4-point scheme on 'path' in a convolutional manner
-        for (i, points) in path.path.windows(4).enumerate() {
-            new_points.push(points[1]);
-            new_corners.push(corners[i + 1]);
-
-            // Do not smooth out corners
-            if corners[i + 1] || corners[i + 2] {
-                continue;
-            }
-
-            // Threshold on segment length of the segment to be broken down
-            let checked_segment_length = points[1].distance_to(points[2]);
-            if checked_segment_length >= min_segment_length {
-                new_points.push(find_new_point_from_4_point_scheme(
-                    &points[1],
-                    &points[2],
-                    &points[0],
-                    &points[3],
-                    outset_ratio,
-                ));
-                new_corners.push(false); // New point must be a non-corner during subdivision
-            }
-        }
+        // Both tangents point along the base line when they agree perfectly
+        let tangent_agreement = (tail1.tangent.get_normalized().dot(base_direction)
+            - tail2.tangent.get_normalized().dot(base_direction))
+            / 2.0;
+        let tangent_factor = (1.0 + tangent_agreement) / 2.0;
 
-        // Push the original last point
-        new_points.extend(path.iter().rev().take(1));
-        new_corners.push(corners[corners.len() - 1]);
+        // The curvature at the end of tail2 flips its sign when travelling from tail1 to tail2
+        let curvature_change = (tail1.curvature + tail2.curvature).abs() * base_length;
+        let curvature_factor = 1.0 / (1.0 + curvature_change);
 
-        assert_eq!(new_points.len(), new_corners.len());
+        let hole_size = std::cmp::max(self.hole_rect.width(), self.hole_rect.height()) as f64;
+        let distance_factor = hole_size / (hole_size + base_length);
 
-        if new_points.len() == path.len() {
-            // no additional points after this step
-            true
+        let correction_factor = if correct_tail_tangents {
+            self.config.corrected_tangents_confidence_factor
         } else {
-            *path = PathF64::from_points(new_points);
-            *corners = new_corners;
-            false
-        }
-    }
-
-    /// Calculate the weighted average tangent vector at the tail of 'path'.
-    /// Either the last 'n' points, the most number of points at the tail such that the sum of segment
-    /// lengths is at most base_length, or the last points until a corner is seen, whichever is the smallest,
-    /// are taken into account.
-    /// The weights are stronger towards the tail, this is specified by 'tail_weight_multiplier'.
-    /// The behavior is undefined unless path is open and 1 < n <= path.len().
-    fn calculate_weighted_average_tangent_at_tail(
-        path: PathF64,
-        corners: &[bool],
-        n: usize,
-        base_length: f64,
-        tail_weight_multiplier: f64,
-    ) -> PointF64 {
-        let len = path.len();
-        assert!(1 < n);
-        assert!(n <= len);
-
-        let mut tangent_acc = PointF64::default();
-        let mut length_acc = 0.0;
-        let rev_points: Vec<PointF64> = path.path.into_iter().rev().take(n).collect();
-        let rev_corners: Vec<&bool> = corners.iter().rev().take(n).collect();
-        for (i, point_pair) in rev_points.windows(2).enumerate() {
-            // Stop at first corner from tail
-            if *rev_corners[i] {
-                break;
-            }
+            1.0
+        };
 
-            let (from, to) = (point_pair[1], point_pair[0]);
-            let from_to = to - from;
-            tangent_acc *= tail_weight_multiplier; // Stronger weights towards the tail (multiplied more times)
-            tangent_acc += from_to.get_normalized();
+        tangent_factor * curvature_factor * distance_factor * correction_factor
+    }
 
-            length_acc += from_to.norm();
-            if length_acc >= base_length {
-                break;
-            }
+    /// If both tails are straight and their extensions intersect inside the hole,
+    /// return the polyline from one endpoint to the other through the intersection.
+    fn calculate_sharp_corner(&self, tail1: &Tail, tail2: &Tail) -> Option<CompoundPath> {
+        let (direction1, direction2) = (tail1.straight_direction?, tail2.straight_direction?);
+
+        let corner = match calculate_intersection(
+            tail1.point,
+            tail1.point + direction1,
+            tail2.point,
+            tail2.point + direction2,
+            self.config.parallel_tolerance,
+        ) {
+            LineIntersectionResult::Intersect(corner) => corner,
+            _ => return None,
+        };
+        if !self
+            .hole_rect
+            .have_point_on_boundary_or_inside(corner.to_point_i32(), 1)
+        {
+            return None;
         }
 
-        tangent_acc.get_normalized()
+        let mut compound_path = CompoundPath::new();
+        compound_path.add_path_f64(PathF64::from_points(vec![tail1.point, corner, tail2.point]));
+        Some(compound_path)
     }
 
     /// Make sure the two tangents do not bend outwards
@@ -307,225 +378,123 @@ impl<'a> CurveIntrapolator<'a> {
 
         (tangent1, tangent2)
     }
+}
 
-    fn calculate_whole_curve(
-        &self,
-        from_point: PointF64,
-        from_tangent: PointF64,
-        to_point: PointF64,
-        to_tangent: PointF64,
-    ) -> Option<CompoundPath> {
-        let intersection_result = calculate_intersection(
-            from_point,
-            from_point + from_tangent,
-            to_point,
-            to_point + to_tangent,
-        );
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::debugger::DummyDebugger;
+    use visioniechor::CompoundPathElement;
+
+    fn tail(point: (f64, f64), tangent: (f64, f64), curvature: f64) -> Tail {
+        let point = PointF64::new(point.0, point.1);
+        Tail {
+            point,
+            smoothed_path: PathF64::from_points(vec![point]),
+            corners: vec![false],
+            tangent: PointF64::new(tangent.0, tangent.1).get_normalized(),
+            curvature,
+            confidence: 1.0,
+            straight_direction: None,
+        }
+    }
 
-        let mut compound_path = CompoundPath::new();
+    fn curve_confidence(tail1: &Tail, tail2: &Tail, correct_tail_tangents: bool) -> f64 {
+        let config = CurveIntrapolatorConfig::default();
+        let curve_model = config.curve_model();
+        CurveIntrapolator::new(
+            config,
+            BoundingRect::new_x_y_w_h(0, 0, 40, 40),
+            curve_model.as_ref(),
+            &DummyDebugger,
+        )
+        .calculate_curve_confidence(tail1, tail2, correct_tail_tangents)
+    }
 
-        match intersection_result {
-            LineIntersectionResult::Intersect(_) => {
-                // Only 1 big part
-                let spline = self.calculate_part_curve(
-                    from_point,
-                    from_tangent,
-                    to_point,
-                    to_tangent,
-                    intersection_result,
-                )?;
-                compound_path.add_spline(spline);
-            }
-            LineIntersectionResult::Parallel => {
-                if from_tangent.dot(to_tangent).is_sign_positive() {
-                    // Same direction
-                    // Only 1 big part
-                    let spline = self.calculate_part_curve(
-                        from_point,
-                        from_tangent,
-                        to_point,
-                        to_tangent,
-                        LineIntersectionResult::Parallel,
-                    )?;
-                    compound_path.add_spline(spline);
-                } else {
-                    self.cut_two_curves_and_insert(
-                        &mut compound_path,
-                        from_point,
-                        from_tangent,
-                        to_point,
-                        to_tangent,
-                    )?;
-                }
-            }
-            LineIntersectionResult::Coincidence => {
-                // Just a straight line
-                let line = self.calculate_part_curve(
-                    from_point,
-                    from_tangent,
-                    to_point,
-                    to_tangent,
-                    LineIntersectionResult::Intersect(calculate_midpoint(from_point, to_point)),
-                )?;
-                compound_path.add_spline(line);
-            }
-            LineIntersectionResult::None => {
-                self.cut_two_curves_and_insert(
-                    &mut compound_path,
-                    from_point,
-                    from_tangent,
-                    to_point,
-                    to_tangent,
-                )?;
-            }
-        };
+    #[test]
+    fn corrected_tangents_lower_curve_confidence() {
+        // GIVEN
+        // A short straight gap whose tangents agree with the base line
+        let (tail1, tail2) = (
+            tail((10.0, 20.0), (1.0, 0.0), 0.0),
+            tail((30.0, 20.0), (-1.0, 0.0), 0.0),
+        );
 
-        Some(compound_path)
-    }
+        // WHEN
+        let clean_confidence = curve_confidence(&tail1, &tail2, false);
+        let corrected_confidence = curve_confidence(&tail1, &tail2, true);
 
-    fn cut_two_curves_and_insert(
-        &self,
-        compound_path: &mut CompoundPath,
-        point_a: PointF64,
-        tangent_a: PointF64,
-        point_b: PointF64,
-        tangent_b: PointF64,
-    ) -> Option<()> {
-        // S-shape detected
-        // Divide into 2 parts and concatenate
-        let mid_point = calculate_midpoint(point_a, point_b);
-        let normal = calculate_unit_normal_of_line(point_a, point_b);
-        // Determine the normal to use (+/-) based on the side of the tangents
-        let from_side_normal = if tangent_a.dot(normal) > 0.0 {
-            normal
-        } else {
-            -normal
-        };
-        let to_side_normal = -from_side_normal;
-        // Calculate the two parts of the curve, recalculating the intersections
-        let from_side_curve = self.calculate_part_curve(
-            point_a,
-            tangent_a,
-            mid_point,
-            from_side_normal,
-            LineIntersectionResult::None,
-        )?;
-        let to_side_curve = self.calculate_part_curve(
-            mid_point,
-            to_side_normal,
-            point_b,
-            tangent_b,
-            LineIntersectionResult::None,
-        )?;
-
-        compound_path.add_spline(from_side_curve);
-        compound_path.add_spline(to_side_curve);
-
-        Some(())
+        // THEN
+        assert!(corrected_confidence < clean_confidence);
     }
 
-    /// Calculate the cubic bezier curve from 'from_point' to 'to_point' with the provided tangents.
-    /// 'intersection_result' is only to avoid unnecessary recalculation.
-    fn calculate_part_curve(
-        &self,
-        from_point: PointF64,
-        from_tangent: PointF64,
-        to_point: PointF64,
-        to_tangent: PointF64,
-        whole_intersection_result: LineIntersectionResult,
-    ) -> Option<Spline> {
-        let retract_ratio = self.config.control_points_retract_ratio;
-        let retract_predicate = |cp: PointF64| {
-            self.hole_rect
-                .have_point_on_boundary_or_inside(cp.to_point_i32(), 1)
-        };
-        let retract_max_n = Some(1000);
-
-        let (control_point1, control_point2) = match whole_intersection_result {
-            LineIntersectionResult::Intersect(intersection) => self
-                .evaluate_control_points_with_intersection(
-                    from_point,
-                    from_tangent,
-                    to_point,
-                    to_tangent,
-                    intersection,
-                ),
-            LineIntersectionResult::Parallel => (from_point + from_tangent, to_point + to_tangent),
-            LineIntersectionResult::Coincidence => panic!("Part curves do not handle coincidence."),
-            LineIntersectionResult::None => {
-                // Whole curve has been divided -> recalculate intersection
-                let intersection_result = calculate_intersection(
-                    from_point,
-                    from_point + from_tangent,
-                    to_point,
-                    to_point + to_tangent,
-                );
-                match intersection_result {
-                    LineIntersectionResult::Intersect(intersection) => self
-                        .evaluate_control_points_with_intersection(
-                            from_point,
-                            from_tangent,
-                            to_point,
-                            to_tangent,
-                            intersection,
-                        ),
-                    LineIntersectionResult::Parallel => {
-                        (from_point + from_tangent, to_point + to_tangent)
-                    }
-                    LineIntersectionResult::Coincidence => {
-                        panic!("Part curves do not handle coincidence.")
-                    }
-                    LineIntersectionResult::None => return None,
-                }
-            }
-        };
-
-        let (control_point1, control_point2) = (
-            retract_point(
-                control_point1,
-                from_point,
-                retract_ratio,
-                retract_predicate,
-                retract_max_n,
-            ),
-            retract_point(
-                control_point2,
-                to_point,
-                retract_ratio,
-                retract_predicate,
-                retract_max_n,
-            ),
+    #[test]
+    fn long_bending_curves_have_lower_confidence() {
+        // GIVEN
+        // A short straight gap, and a long gap across the hole whose tangents and curvatures disagree
+        let (clean_tail1, clean_tail2) = (
+            tail((10.0, 20.0), (1.0, 0.0), 0.0),
+            tail((30.0, 20.0), (-1.0, 0.0), 0.0),
+        );
+        let (bending_tail1, bending_tail2) = (
+            tail((0.0, 5.0), (0.0, 1.0), 0.05),
+            tail((40.0, 35.0), (0.0, -1.0), 0.05),
         );
-        let mut spline = Spline::new(from_point);
-        spline.add(control_point1, control_point2, to_point);
-        Some(spline)
-    }
 
-    fn evaluate_control_points_with_intersection(
-        &self,
-        from_point: PointF64,
-        from_tangent: PointF64,
-        to_point: PointF64,
-        to_tangent: PointF64,
-        intersection: PointF64,
-    ) -> (PointF64, PointF64) {
-        let scaled_base_length = from_point.distance_to(to_point) * 2.0;
+        // WHEN
+        let clean_confidence = curve_confidence(&clean_tail1, &clean_tail2, false);
+        let bending_confidence = curve_confidence(&bending_tail1, &bending_tail2, false);
 
-        let length_from_and_intersection = from_point.distance_to(intersection);
-        let length_to_and_intersection = to_point.distance_to(intersection);
+        // THEN
+        assert!(bending_confidence < clean_confidence);
+        assert!(bending_confidence < 0.5 * clean_confidence);
+    }
 
-        let calculate_control_point =
-            |point: PointF64, tangent: PointF64, length_with_intersection: f64| {
-                if scaled_base_length > length_with_intersection * 0.5 {
-                    calculate_midpoint(point, intersection)
-                } else {
-                    point + tangent * scaled_base_length
-                }
-            };
+    #[test]
+    fn straight_tails_at_right_angle_meet_at_their_intersection() {
+        // GIVEN
+        // A horizontal tail entering the hole at (0, 10) and a vertical tail entering it at (25, 40)
+        let hole_rect = BoundingRect::new_x_y_w_h(0, 0, 40, 40);
+        let config = CurveIntrapolatorConfig {
+            preserve_corners: true,
+            ..Default::default()
+        };
+        let curve_model = config.curve_model();
+        let intrapolator =
+            CurveIntrapolator::new(config, hole_rect, curve_model.as_ref(), &DummyDebugger);
+        let horizontal_line = |x: f64| PointF64::new(x, 10.0);
+        let vertical_line = |y: f64| PointF64::new(25.0, y);
+        let curve1 = PathF64::from_points(
+            vec![-30.0, -20.0, -10.0, 0.0]
+                .into_iter()
+                .map(horizontal_line)
+                .collect(),
+        );
+        let curve2 = PathF64::from_points(
+            vec![70.0, 60.0, 50.0, 40.0]
+                .into_iter()
+                .map(vertical_line)
+                .collect(),
+        );
 
-        (
-            calculate_control_point(from_point, from_tangent, length_from_and_intersection),
-            calculate_control_point(to_point, to_tangent, length_to_and_intersection),
-        )
+        // WHEN
+        let intrapolated_curve = intrapolator
+            .intrapolate_curve_between_curves(curve1, curve2, None, true, true, false)
+            .unwrap();
+
+        // THEN
+        let elements: Vec<&CompoundPathElement> = intrapolated_curve.curve.iter().collect();
+        assert_eq!(elements.len(), 1);
+        match elements[0] {
+            CompoundPathElement::PathF64(polyline) => assert_eq!(
+                polyline.path,
+                vec![
+                    horizontal_line(0.0),
+                    PointF64::new(25.0, 10.0),
+                    vertical_line(40.0)
+                ]
+            ),
+            _ => panic!("The curve is not a polyline"),
+        }
     }
 }
@@ -0,0 +1,624 @@
+use visioniechor::{BoundingRect, CompoundPath, PointF64, Spline};
+
+use crate::{
+    biarc::CircularArc,
+    clothoid::Clothoid,
+    geo::{
+        calculate_bending_energy, calculate_in_between_point, calculate_intersection,
+        calculate_midpoint, calculate_unit_normal_of_line, retract_point, rotate_vector,
+        LineIntersectionResult,
+    },
+    quintic::QuinticHermite,
+    refinement::{CurveRefiner, RefinementConfig},
+    tail::Tail,
+};
+
+/// A gap in a shape between two endpoints, to be bridged by a curve.
+/// The tangents of both tails point into the hole.
+pub struct Gap {
+    pub from: Tail,
+    pub to: Tail,
+    pub hole_rect: BoundingRect,
+    /// Maximum distance by which the curve may leave 'hole_rect' (see 'CurveIntrapolatorConfig::hole_boundary_tolerance')
+    pub hole_boundary_tolerance: f64,
+}
+
+/// A generator of the curves bridging gaps
+pub trait CurveModel {
+    /// Construct the curve from 'gap.from.point' to 'gap.to.point', which leaves along 'gap.from.tangent'
+    /// and enters against 'gap.to.tangent'.
+    /// Return None if the gap cannot be bridged by this model.
+    fn intrapolate(&self, gap: &Gap) -> Option<CompoundPath>;
+}
+
+/// One cubic bezier curve with control points placed by the tangent intersection,
+/// or several of them for S-shapes, joined at the inflection points which minimize the bending energy.
+/// Control points outside the hole are retracted towards their endpoints, unless the curves are refined.
+/// S-shapes are divided at the inflection point chosen before refinement.
+pub struct BezierCurveModel {
+    pub control_points_retract_ratio: f64,
+    /// Maximum number of curves bridging a single gap, at least 2 for S-shapes to be bridged at all
+    pub max_pieces: usize,
+    /// See 'CurveIntrapolatorConfig::parallel_tolerance'
+    pub parallel_tolerance: f64,
+    /// If not None, the lengths of the control points are optimized (see 'CurveRefiner') instead of retracted,
+    /// and a gap is not bridged if no lengths are feasible
+    pub refinement_config: Option<RefinementConfig>,
+}
+
+/// An Euler spiral (clothoid) matching the tangents at both endpoints
+pub struct ClothoidCurveModel {
+    /// If true, clothoids are output as cubic bezier curves, otherwise as polylines
+    pub as_bezier: bool,
+}
+
+/// Two tangent-continuous circular arcs, or a single one if a circle fits both tangents and tail curvatures
+pub struct BiarcCurveModel {
+    pub single_arc_tolerance: f64,
+}
+
+/// A quintic curve matching both the tangents and the curvatures at the endpoints (G2).
+/// The curve is output as a polyline.
+pub struct CurvatureContinuousCurveModel;
+
+impl CurveModel for BezierCurveModel {
+    fn intrapolate(&self, gap: &Gap) -> Option<CompoundPath> {
+        let fitter = BezierCurveFitter {
+            retract_ratio: self.control_points_retract_ratio,
+            max_pieces: self.max_pieces,
+            parallel_tolerance: self.parallel_tolerance,
+            hole_rect: gap.hole_rect,
+            refiner: self.refinement_config.map(|config| {
+                CurveRefiner::new(config, gap.hole_rect, gap.hole_boundary_tolerance)
+            }),
+        };
+        fitter.calculate_whole_curve(
+            gap.from.point,
+            gap.from.tangent,
+            gap.to.point,
+            gap.to.tangent,
+            (gap.from.confidence, gap.to.confidence),
+        )
+    }
+}
+
+impl CurveModel for ClothoidCurveModel {
+    fn intrapolate(&self, gap: &Gap) -> Option<CompoundPath> {
+        let clothoid = Clothoid::fit_g1(
+            gap.from.point,
+            gap.from.tangent,
+            gap.to.point,
+            -gap.to.tangent,
+        )?;
+
+        let mut compound_path = CompoundPath::new();
+        if self.as_bezier {
+            clothoid
+                .to_splines(std::f64::consts::FRAC_PI_4)
+                .into_iter()
+                .for_each(|spline| compound_path.add_spline(spline));
+        } else {
+            compound_path.add_path_f64(clothoid.to_path(1.0));
+        }
+
+        Some(compound_path)
+    }
+}
+
+impl CurveModel for BiarcCurveModel {
+    fn intrapolate(&self, gap: &Gap) -> Option<CompoundPath> {
+        let arcs = CircularArc::fit_biarc(
+            gap.from.point,
+            gap.from.tangent,
+            gap.to.point,
+            -gap.to.tangent,
+            // The curve travels away from 'gap.to.point' along its tail, so its curvature is negated there
+            Some((gap.from.curvature, -gap.to.curvature)),
+            self.single_arc_tolerance,
+        )?;
+
+        let mut compound_path = CompoundPath::new();
+        arcs.iter()
+            .flat_map(|arc| arc.to_splines(std::f64::consts::FRAC_PI_2))
+            .for_each(|spline| compound_path.add_spline(spline));
+
+        Some(compound_path)
+    }
+}
+
+impl CurveModel for CurvatureContinuousCurveModel {
+    fn intrapolate(&self, gap: &Gap) -> Option<CompoundPath> {
+        if gap.from.point.distance_to(gap.to.point) < f64::EPSILON {
+            return None;
+        }
+
+        // The curve travels away from 'gap.to.point' along its tail, so its curvature is negated there
+        let quintic = QuinticHermite::from_directions_and_curvatures(
+            gap.from.point,
+            gap.from.tangent,
+            gap.from.curvature,
+            gap.to.point,
+            -gap.to.tangent,
+            -gap.to.curvature,
+        );
+
+        let mut compound_path = CompoundPath::new();
+        compound_path.add_path_f64(quintic.to_path(1.0));
+        Some(compound_path)
+    }
+}
+
+/// The bezier curve model applied to a specific hole
+struct BezierCurveFitter {
+    retract_ratio: f64,
+    max_pieces: usize,
+    parallel_tolerance: f64,
+    hole_rect: BoundingRect,
+    refiner: Option<CurveRefiner>,
+}
+
+/// Positions of candidate inflection points along the base line, as ratios of the base length from the start
+const INFLECTION_POSITION_RATIOS: [f64; 3] = [0.35, 0.5, 0.65];
+/// Offsets of candidate inflection points from the base line, as ratios of the base length
+const INFLECTION_OFFSET_RATIOS: [f64; 3] = [-0.15, 0.0, 0.15];
+/// Rotations (in radians) of candidate inflection tangents from the normal of the base line
+const INFLECTION_ROTATIONS: [f64; 5] = [
+    -std::f64::consts::FRAC_PI_4,
+    -std::f64::consts::FRAC_PI_8,
+    0.0,
+    std::f64::consts::FRAC_PI_8,
+    std::f64::consts::FRAC_PI_4,
+];
+
+// Helper functions
+impl BezierCurveFitter {
+    /// 'confidences' are the confidences of the tails at 'from_point' and 'to_point' respectively.
+    /// Return None if the gap cannot be bridged, or if refinement is enabled and any of the curves cannot be refined.
+    fn calculate_whole_curve(
+        &self,
+        from_point: PointF64,
+        from_tangent: PointF64,
+        to_point: PointF64,
+        to_tangent: PointF64,
+        confidences: (f64, f64),
+    ) -> Option<CompoundPath> {
+        let intersection_result = calculate_intersection(
+            from_point,
+            from_point + from_tangent,
+            to_point,
+            to_point + to_tangent,
+            self.parallel_tolerance,
+        );
+
+        let splines = match intersection_result {
+            LineIntersectionResult::Intersect(_) => {
+                // Only 1 big part
+                vec![self.calculate_part_curve(
+                    from_point,
+                    from_tangent,
+                    to_point,
+                    to_tangent,
+                    intersection_result,
+                    confidences,
+                )?]
+            }
+            LineIntersectionResult::Parallel => {
+                if from_tangent.dot(to_tangent).is_sign_positive() {
+                    // Same direction
+                    // Only 1 big part
+                    vec![self.calculate_part_curve(
+                        from_point,
+                        from_tangent,
+                        to_point,
+                        to_tangent,
+                        LineIntersectionResult::Parallel,
+                        confidences,
+                    )?]
+                } else {
+                    self.fit_s_curve(
+                        from_point,
+                        from_tangent,
+                        to_point,
+                        to_tangent,
+                        confidences,
+                        self.max_pieces,
+                    )?
+                }
+            }
+            LineIntersectionResult::Coincidence => {
+                // Just a straight line
+                vec![self.calculate_part_curve(
+                    from_point,
+                    from_tangent,
+                    to_point,
+                    to_tangent,
+                    LineIntersectionResult::Intersect(calculate_midpoint(from_point, to_point)),
+                    confidences,
+                )?]
+            }
+            LineIntersectionResult::None => self.fit_s_curve(
+                from_point,
+                from_tangent,
+                to_point,
+                to_tangent,
+                confidences,
+                self.max_pieces,
+            )?,
+        };
+
+        // Refine the chosen curves only, so that the candidates of S-shapes are compared before refinement.
+        // The curve is rejected if any of them cannot be refined.
+        let splines = match &self.refiner {
+            Some(refiner) => splines
+                .iter()
+                .map(|spline| refiner.refine_spline(spline, &[], 0.0))
+                .collect::<Option<Vec<Spline>>>()?,
+            None => splines,
+        };
+
+        let mut compound_path = CompoundPath::new();
+        splines
+            .into_iter()
+            .for_each(|spline| compound_path.add_spline(spline));
+        Some(compound_path)
+    }
+
+    /// S-shape detected.
+    /// Divide the gap into parts at an inflection point, and concatenate the curves of the parts.
+    /// The inflection point and its tangent are chosen among candidates around the midpoint of the endpoints,
+    /// such that the total bending energy of the curves is the minimum.
+    /// A part whose tangents have no forward intersection is divided again, as long as the whole gap
+    /// is bridged by at most 'max_pieces' curves.
+    /// Return None if no candidate can be bridged.
+    fn fit_s_curve(
+        &self,
+        point_a: PointF64,
+        tangent_a: PointF64,
+        point_b: PointF64,
+        tangent_b: PointF64,
+        confidences: (f64, f64),
+        max_pieces: usize,
+    ) -> Option<Vec<Spline>> {
+        if max_pieces < 2 {
+            return None;
+        }
+        let (max_pieces_a, max_pieces_b) = (max_pieces / 2, max_pieces - max_pieces / 2);
+
+        let base = point_b - point_a;
+        let normal = calculate_unit_normal_of_line(point_a, point_b);
+        // Determine the normal to use (+/-) based on the side of the tangents
+        let from_side_normal = if tangent_a.dot(normal) > 0.0 {
+            normal
+        } else {
+            -normal
+        };
+        // The direction of travel at the inflection point
+        let to_side_normal = -from_side_normal;
+        // The direction at the inflection point is constructed, not estimated
+        let (confidence_a, confidence_b) = confidences;
+
+        let candidates = INFLECTION_POSITION_RATIOS
+            .iter()
+            .flat_map(|&position_ratio| {
+                INFLECTION_OFFSET_RATIOS
+                    .iter()
+                    .flat_map(move |&offset_ratio| {
+                        INFLECTION_ROTATIONS.iter().map(move |&rotation| {
+                            (
+                                point_a
+                                    + base * position_ratio
+                                    + from_side_normal * (base.norm() * offset_ratio),
+                                rotate_vector(to_side_normal, rotation),
+                            )
+                        })
+                    })
+            });
+
+        candidates
+            .filter_map(|(inflection_point, inflection_direction)| {
+                // Calculate the parts of the curve, recalculating the intersections
+                let mut splines = self.fit_part_curves(
+                    point_a,
+                    tangent_a,
+                    inflection_point,
+                    -inflection_direction,
+                    (confidence_a, 1.0),
+                    max_pieces_a,
+                )?;
+                splines.extend(self.fit_part_curves(
+                    inflection_point,
+                    inflection_direction,
+                    point_b,
+                    tangent_b,
+                    (1.0, confidence_b),
+                    max_pieces_b,
+                )?);
+                let energy: f64 = splines
+                    .iter()
+                    .map(|spline| calculate_bending_energy(&spline.points))
+                    .sum();
+                Some((energy, splines))
+            })
+            .min_by(|(energy1, _), (energy2, _)| energy1.total_cmp(energy2))
+            .map(|(_, splines)| splines)
+    }
+
+    /// Bridge a part of an S-shaped gap by a single curve, or by at most 'max_pieces' curves if it is an S-shape again.
+    fn fit_part_curves(
+        &self,
+        from_point: PointF64,
+        from_tangent: PointF64,
+        to_point: PointF64,
+        to_tangent: PointF64,
+        confidences: (f64, f64),
+        max_pieces: usize,
+    ) -> Option<Vec<Spline>> {
+        match self.calculate_part_curve(
+            from_point,
+            from_tangent,
+            to_point,
+            to_tangent,
+            LineIntersectionResult::None,
+            confidences,
+        ) {
+            Some(spline) => Some(vec![spline]),
+            None => self.fit_s_curve(
+                from_point,
+                from_tangent,
+                to_point,
+                to_tangent,
+                confidences,
+                max_pieces,
+            ),
+        }
+    }
+
+    /// Calculate the cubic bezier curve from 'from_point' to 'to_point' with the provided tangents,
+    /// whose control points are retracted into the hole.
+    /// 'intersection_result' is only to avoid unnecessary recalculation.
+    fn calculate_part_curve(
+        &self,
+        from_point: PointF64,
+        from_tangent: PointF64,
+        to_point: PointF64,
+        to_tangent: PointF64,
+        whole_intersection_result: LineIntersectionResult,
+        confidences: (f64, f64),
+    ) -> Option<Spline> {
+        let retract_ratio = self.retract_ratio;
+        let retract_predicate = |cp: PointF64| {
+            self.hole_rect
+                .have_point_on_boundary_or_inside(cp.to_point_i32(), 1)
+        };
+        let retract_max_n = Some(1000);
+
+        let (control_point1, control_point2) = match whole_intersection_result {
+            LineIntersectionResult::Intersect(intersection) => self
+                .evaluate_control_points_with_intersection(
+                    from_point,
+                    from_tangent,
+                    to_point,
+                    to_tangent,
+                    intersection,
+                    confidences,
+                ),
+            LineIntersectionResult::Parallel => (from_point + from_tangent, to_point + to_tangent),
+            LineIntersectionResult::Coincidence => panic!("Part curves do not handle coincidence."),
+            LineIntersectionResult::None => {
+                // Whole curve has been divided -> recalculate intersection
+                let intersection_result = calculate_intersection(
+                    from_point,
+                    from_point + from_tangent,
+                    to_point,
+                    to_point + to_tangent,
+                    self.parallel_tolerance,
+                );
+                match intersection_result {
+                    LineIntersectionResult::Intersect(intersection) => self
+                        .evaluate_control_points_with_intersection(
+                            from_point,
+                            from_tangent,
+                            to_point,
+                            to_tangent,
+                            intersection,
+                            confidences,
+                        ),
+                    LineIntersectionResult::Parallel => {
+                        (from_point + from_tangent, to_point + to_tangent)
+                    }
+                    // A part whose tangents lie on the same line cannot be bridged by a single curve
+                    LineIntersectionResult::Coincidence | LineIntersectionResult::None => {
+                        return None
+                    }
+                }
+            }
+        };
+
+        // Retract the control points into the hole
+        let (control_point1, control_point2) = (
+            retract_point(
+                control_point1,
+                from_point,
+                retract_ratio,
+                retract_predicate,
+                retract_max_n,
+            ),
+            retract_point(
+                control_point2,
+                to_point,
+                retract_ratio,
+                retract_predicate,
+                retract_max_n,
+            ),
+        );
+        let mut spline = Spline::new(from_point);
+        spline.add(control_point1, control_point2, to_point);
+        Some(spline)
+    }
+
+    /// The control point of an endpoint follows its tangent as much as its confidence allows,
+    /// the rest is taken by the control point of a straight line between the endpoints.
+    fn evaluate_control_points_with_intersection(
+        &self,
+        from_point: PointF64,
+        from_tangent: PointF64,
+        to_point: PointF64,
+        to_tangent: PointF64,
+        intersection: PointF64,
+        confidences: (f64, f64),
+    ) -> (PointF64, PointF64) {
+        let scaled_base_length = from_point.distance_to(to_point) * 2.0;
+
+        let length_from_and_intersection = from_point.distance_to(intersection);
+        let length_to_and_intersection = to_point.distance_to(intersection);
+
+        let calculate_control_point = |point: PointF64,
+                                       tangent: PointF64,
+                                       length_with_intersection: f64,
+                                       other_point: PointF64,
+                                       confidence: f64| {
+            let tangent_control_point = if scaled_base_length > length_with_intersection * 0.5 {
+                calculate_midpoint(point, intersection)
+            } else {
+                point + tangent * scaled_base_length
+            };
+            let straight_control_point = calculate_in_between_point(point, other_point, 1.0 / 3.0);
+            calculate_in_between_point(straight_control_point, tangent_control_point, confidence)
+        };
+
+        let (from_confidence, to_confidence) = confidences;
+        (
+            calculate_control_point(
+                from_point,
+                from_tangent,
+                length_from_and_intersection,
+                to_point,
+                from_confidence,
+            ),
+            calculate_control_point(
+                to_point,
+                to_tangent,
+                length_to_and_intersection,
+                from_point,
+                to_confidence,
+            ),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::curve::CurveIntrapolatorConfig;
+    use visioniechor::PathF64;
+
+    fn tail(point: PointF64, tangent: PointF64) -> Tail {
+        Tail {
+            point,
+            smoothed_path: PathF64::new(),
+            corners: vec![],
+            tangent: tangent.get_normalized(),
+            curvature: 0.0,
+            confidence: 1.0,
+            straight_direction: None,
+        }
+    }
+
+    #[test]
+    fn bezier_model_bridges_wavy_gap_with_more_than_two_pieces() {
+        // GIVEN
+        let gap = Gap {
+            from: tail(PointF64::new(12.0, 34.0), PointF64::new(-0.6, -0.8)),
+            to: tail(PointF64::new(20.0, 0.5), PointF64::new(-0.48, -0.88)),
+            hole_rect: BoundingRect::new_x_y_w_h(0, 0, 40, 40),
+            hole_boundary_tolerance: 1.0,
+        };
+        let model = |max_pieces| BezierCurveModel {
+            control_points_retract_ratio: 0.4,
+            max_pieces,
+            parallel_tolerance: 1e-3,
+            refinement_config: None,
+        };
+
+        // WHEN
+        let two_pieces = model(2).intrapolate(&gap);
+        let four_pieces = model(4).intrapolate(&gap);
+
+        // THEN
+        assert!(two_pieces.is_none());
+        let curves: Vec<Spline> = four_pieces
+            .unwrap()
+            .paths
+            .into_iter()
+            .filter_map(|path| match path {
+                visioniechor::CompoundPathElement::Spline(spline) => Some(spline),
+                _ => None,
+            })
+            .collect();
+        assert!(curves.len() > 2 && curves.len() <= 4);
+        assert_eq!(curves[0].points[0], gap.from.point);
+        assert_eq!(*curves.last().unwrap().points.last().unwrap(), gap.to.point);
+        curves.windows(2).for_each(|pair| {
+            assert_eq!(pair[0].points.last(), pair[1].points.first());
+        });
+    }
+
+    #[test]
+    fn bezier_model_treats_nearly_parallel_tangents_as_parallel() {
+        // GIVEN
+        // A U-turn whose tangents differ by 5e-3 radians, so that they meet about 2000 pixels away
+        let u_turn = |to_tangent| Gap {
+            from: tail(PointF64::new(5.0, 10.0), PointF64::new(1.0, 0.0)),
+            to: tail(PointF64::new(5.0, 20.0), to_tangent),
+            hole_rect: BoundingRect::new_x_y_w_h(0, 0, 40, 40),
+            hole_boundary_tolerance: 1.0,
+        };
+        let model = CurveIntrapolatorConfig::default().curve_model();
+        let control_points = |gap: &Gap| match model.intrapolate(gap).unwrap().paths.remove(0) {
+            visioniechor::CompoundPathElement::Spline(spline) => spline.points,
+            _ => panic!("Expected a bezier curve"),
+        };
+
+        // WHEN
+        let parallel_points = control_points(&u_turn(PointF64::new(1.0, 0.0)));
+        let nearly_parallel_points = control_points(&u_turn(PointF64::new(1.0, -0.005)));
+
+        // THEN
+        // The curve does not jump to the far intersection of the tangents
+        parallel_points
+            .iter()
+            .zip(nearly_parallel_points.iter())
+            .for_each(|(&a, &b)| assert!(a.distance_to(b) < 0.1));
+    }
+
+    #[test]
+    fn bezier_model_rejects_gap_without_feasible_refinement() {
+        // GIVEN
+        // Both tangents point out of the right side of the hole, so that any control points
+        // at least 5 pixels from their endpoints take the curve more than 1 pixel out of the hole
+        let gap = Gap {
+            from: tail(PointF64::new(20.0, 5.0), PointF64::new(1.0, 0.0)),
+            to: tail(PointF64::new(20.0, 15.0), PointF64::new(1.0, 0.0)),
+            hole_rect: BoundingRect::new_x_y_w_h(0, 0, 20, 20),
+            hole_boundary_tolerance: 1.0,
+        };
+        let model = |refinement_config| BezierCurveModel {
+            control_points_retract_ratio: 0.4,
+            max_pieces: 4,
+            parallel_tolerance: 1e-3,
+            refinement_config,
+        };
+        let refinement_config = RefinementConfig {
+            min_length_ratio: 0.5,
+            ..Default::default()
+        };
+
+        // WHEN
+        let retracted = model(None).intrapolate(&gap);
+        let refined = model(Some(refinement_config)).intrapolate(&gap);
+
+        // THEN
+        assert!(retracted.is_some());
+        assert!(refined.is_none());
+    }
+}
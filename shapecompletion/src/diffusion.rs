@@ -0,0 +1,174 @@
+use visioniechor::{BinaryImage, PointI32};
+
+use crate::{
+    filler::{ApplyConfig, FilledHoleMatrix},
+    region::{four_neighbors, label_4_connected_regions},
+};
+
+#[derive(Clone, Copy, Debug)]
+pub struct DiffusionConfig {
+    /// Maximum number of Gauss-Seidel sweeps over the hole
+    pub max_iterations: usize,
+    /// The iterations stop once no pixel changes by more than this (in intensity levels) in a sweep
+    pub tolerance: f64,
+    /// Successive over-relaxation factor in [1.0, 2.0), where 1.0 is plain Gauss-Seidel
+    pub relaxation: f64,
+}
+
+impl Default for DiffusionConfig {
+    fn default() -> Self {
+        Self {
+            max_iterations: 2000,
+            tolerance: 0.01,
+            relaxation: 1.8,
+        }
+    }
+}
+
+/// Shade a completed hole in a grayscale image by harmonic (Laplace) interpolation.
+/// The completed shape splits the image into 4-connected regions of foreground (Texture and Structure)
+/// and background (Blank), and the interpolation is solved inside each region separately,
+/// with the visible pixels of the region as boundary values, so that shading never bleeds across contours.
+pub struct DiffusionFiller {
+    pub config: DiffusionConfig,
+}
+
+// API
+impl DiffusionFiller {
+    pub fn new(config: DiffusionConfig) -> Self {
+        Self { config }
+    }
+
+    /// Shade the pixels of 'filled_hole' in the grayscale image 'pixels' in place, where 'pixels' is stored row by row
+    /// with 'shape.width' pixels per row, 'shape' marks the visible shape with the hole blank,
+    /// and the top-left corner of the hole is at 'origin'.
+    /// Pixels in regions without visible pixels, and pixels outside the image, are left unchanged.
+    /// The behavior is undefined unless 'pixels' and 'shape' are of the same size.
+    pub fn fill(
+        &self,
+        pixels: &mut [u8],
+        shape: &BinaryImage,
+        filled_hole: &FilledHoleMatrix,
+        origin: PointI32,
+    ) {
+        let completed_shape =
+            filled_hole.applied_to_binary_image(shape, origin, &ApplyConfig::default());
+        let (labels, num_labels) = label_4_connected_regions(&completed_shape);
+        let index_of = |point: PointI32| point.y as usize * shape.width + point.x as usize;
+        let is_inside_image = |point: PointI32| {
+            0 <= point.x
+                && point.x < shape.width as i32
+                && 0 <= point.y
+                && point.y < shape.height as i32
+        };
+
+        let unknowns: Vec<PointI32> = (0..filled_hole.height)
+            .flat_map(|i| (0..filled_hole.width).map(move |j| (i, j)))
+            .map(|(i, j)| origin + PointI32::new(j as i32, i as i32))
+            .filter(|&point| is_inside_image(point))
+            .collect();
+        let mut is_unknown = vec![false; pixels.len()];
+        unknowns
+            .iter()
+            .for_each(|&point| is_unknown[index_of(point)] = true);
+
+        // The mean of the visible pixels next to the hole in each region is the initial guess
+        let mut boundary_sums = vec![(0.0, 0_usize); num_labels];
+        unknowns.iter().for_each(|&point| {
+            four_neighbors(point)
+                .filter(|&neighbor| {
+                    is_inside_image(neighbor)
+                        && !is_unknown[index_of(neighbor)]
+                        && labels[index_of(neighbor)] == labels[index_of(point)]
+                })
+                .for_each(|neighbor| {
+                    let (sum, count) = &mut boundary_sums[labels[index_of(point)]];
+                    *sum += pixels[index_of(neighbor)] as f64;
+                    *count += 1;
+                });
+        });
+
+        // Regions without boundary values are left as they are
+        let unknowns: Vec<PointI32> = unknowns
+            .into_iter()
+            .filter(|&point| boundary_sums[labels[index_of(point)]].1 > 0)
+            .collect();
+        let mut values: Vec<f64> = pixels.iter().map(|&pixel| pixel as f64).collect();
+        unknowns.iter().for_each(|&point| {
+            let (sum, count) = boundary_sums[labels[index_of(point)]];
+            values[index_of(point)] = sum / count as f64;
+        });
+
+        for _ in 0..self.config.max_iterations {
+            let mut max_change: f64 = 0.0;
+            for &point in unknowns.iter() {
+                let (sum, count) = four_neighbors(point)
+                    .filter(|&neighbor| {
+                        is_inside_image(neighbor)
+                            && labels[index_of(neighbor)] == labels[index_of(point)]
+                    })
+                    .fold((0.0, 0), |(sum, count), neighbor| {
+                        (sum + values[index_of(neighbor)], count + 1)
+                    });
+                if count == 0 {
+                    continue;
+                }
+                let value = &mut values[index_of(point)];
+                let change = self.config.relaxation * (sum / count as f64 - *value);
+                *value += change;
+                max_change = max_change.max(change.abs());
+            }
+            if max_change <= self.config.tolerance {
+                break;
+            }
+        }
+
+        unknowns.iter().for_each(|&point| {
+            pixels[index_of(point)] = values[index_of(point)].round().clamp(0.0, 255.0) as u8;
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filler::FilledHoleElement;
+
+    #[test]
+    fn fill_continues_gradient_without_bleeding_across_contour() {
+        // GIVEN
+        // A vertical gradient on a shape covering the left part of the image, and a flat bright background,
+        // with a hole across the contour between them
+        let (width, height) = (40, 30);
+        let mut shape = BinaryImage::new_w_h(width, height);
+        let mut pixels = vec![250_u8; width * height];
+        for y in 0..height {
+            for x in 0..20 {
+                shape.set_pixel(x, y, true);
+                pixels[y * width + x] = (50 + 5 * y) as u8;
+            }
+        }
+        let origin = PointI32::new(10, 10);
+        let mut filled_hole = FilledHoleMatrix::new(20, 10);
+        for i in 0..10 {
+            for j in 0..20 {
+                shape.set_pixel(10 + j, 10 + i, false);
+                pixels[(10 + i) * width + 10 + j] = 0;
+                if j < 10 {
+                    filled_hole[i][j] = FilledHoleElement::Texture;
+                }
+            }
+        }
+
+        // WHEN
+        DiffusionFiller::new(Default::default()).fill(&mut pixels, &shape, &filled_hole, origin);
+
+        // THEN
+        for y in 10..20 {
+            for x in 10..30 {
+                let expected = if x < 20 { 50 + 5 * y as i32 } else { 250 };
+                assert!((pixels[y * width + x] as i32 - expected).abs() <= 1);
+            }
+        }
+    }
+}
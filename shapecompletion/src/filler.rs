@@ -5,7 +5,29 @@ use std::{
 };
 
 use flo_curves::{bezier::Curve, BezierCurve, Coord2, Coordinate2D};
-use visioniechor::{BinaryImage, BoundingRect, CompoundPath, PointF64, PointI32, PointUsize};
+use visioniechor::{
+    BinaryImage, BoundingRect, Color, ColorImage, CompoundPath, PathI32, PointF64, PointI32,
+    PointUsize,
+};
+
+use crate::{
+    geo::sample_compound_path,
+    region::{flood_fill_4_connected, four_neighbors},
+};
+
+/// Maximum distance between consecutive points when intrapolated curves are flattened into line segments
+const CURVE_FLATTENING_STEP: f64 = 0.25;
+
+/// How the regions inside a hole are chosen to be filled
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FillMode {
+    /// Flood fill from points sampled on each stretch of the hole boundary between endpoints,
+    /// if few pixels just outside the stretch are blank
+    BoundarySampling,
+    /// Close the visible contours with the intrapolated curves into loops,
+    /// and fill the pixels inside an odd number of loops
+    EvenOdd,
+}
 
 #[derive(Clone, Copy, PartialEq)]
 pub enum FilledHoleElement {
@@ -14,6 +36,43 @@ pub enum FilledHoleElement {
     Texture,
 }
 
+/// What a FilledHoleElement becomes when the completion is applied to an image
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AppliedPixel {
+    Foreground,
+    Background,
+    /// The pixel of the image is kept
+    Unchanged,
+}
+
+/// How each FilledHoleElement is applied to an image
+#[derive(Clone, Copy, Debug)]
+pub struct ApplyConfig {
+    pub blank: AppliedPixel,
+    pub structure: AppliedPixel,
+    pub texture: AppliedPixel,
+}
+
+impl Default for ApplyConfig {
+    fn default() -> Self {
+        Self {
+            blank: AppliedPixel::Background,
+            structure: AppliedPixel::Foreground,
+            texture: AppliedPixel::Foreground,
+        }
+    }
+}
+
+impl ApplyConfig {
+    pub fn applied_pixel(&self, element: FilledHoleElement) -> AppliedPixel {
+        match element {
+            FilledHoleElement::Blank => self.blank,
+            FilledHoleElement::Structure => self.structure,
+            FilledHoleElement::Texture => self.texture,
+        }
+    }
+}
+
 pub struct FilledHoleMatrix {
     pub width: usize,
     pub height: usize,
@@ -39,6 +98,17 @@ impl FilledHoleMatrix {
         matrix
     }
 
+    /// Replicate each element into a 'factor' x 'factor' block.
+    pub fn new_upscaled(&self, factor: usize) -> Self {
+        let mut matrix = Self::new(self.width * factor, self.height * factor);
+        for i in 0..matrix.height {
+            for j in 0..matrix.width {
+                matrix[i][j] = self[i / factor][j / factor];
+            }
+        }
+        matrix
+    }
+
     pub fn new_without_row(&self, row: usize) -> Self {
         let mut matrix = Self::new(self.width, self.height - 1);
         for i in 0..matrix.height {
@@ -48,6 +118,214 @@ impl FilledHoleMatrix {
         }
         matrix
     }
+
+    /// Write the completion into 'image' in place, with the top-left corner of the hole at 'origin'.
+    /// Pixels outside the image are skipped.
+    pub fn apply_to_binary_image(
+        &self,
+        image: &mut BinaryImage,
+        origin: PointI32,
+        apply_config: &ApplyConfig,
+    ) {
+        self.for_each_applied_pixel(origin, apply_config, |x, y, foreground| {
+            image.set_pixel_safe(x, y, foreground);
+        });
+    }
+
+    /// Same as 'apply_to_binary_image', but on a copy of 'image'.
+    pub fn applied_to_binary_image(
+        &self,
+        image: &BinaryImage,
+        origin: PointI32,
+        apply_config: &ApplyConfig,
+    ) -> BinaryImage {
+        let mut image = image.clone();
+        self.apply_to_binary_image(&mut image, origin, apply_config);
+        image
+    }
+
+    /// Write the completion into 'image' in place, with the top-left corner of the hole at 'origin',
+    /// painting the foreground with 'foreground' and the background with 'background'.
+    /// Pixels outside the image are skipped.
+    pub fn apply_to_color_image(
+        &self,
+        image: &mut ColorImage,
+        origin: PointI32,
+        apply_config: &ApplyConfig,
+        foreground: Color,
+        background: Color,
+    ) {
+        let (width, height) = (image.width as i32, image.height as i32);
+        self.for_each_applied_pixel(origin, apply_config, |x, y, is_foreground| {
+            if 0 <= x && x < width && 0 <= y && y < height {
+                let color = if is_foreground {
+                    &foreground
+                } else {
+                    &background
+                };
+                image.set_pixel(x as usize, y as usize, color);
+            }
+        });
+    }
+
+    /// Same as 'apply_to_color_image', but on a copy of 'image'.
+    pub fn applied_to_color_image(
+        &self,
+        image: &ColorImage,
+        origin: PointI32,
+        apply_config: &ApplyConfig,
+        foreground: Color,
+        background: Color,
+    ) -> ColorImage {
+        let mut image = image.clone();
+        self.apply_to_color_image(&mut image, origin, apply_config, foreground, background);
+        image
+    }
+}
+
+// Helper functions
+impl FilledHoleMatrix {
+    /// Call 'set_pixel' with the image coordinates of each element not mapped to 'AppliedPixel::Unchanged',
+    /// and whether it becomes foreground.
+    fn for_each_applied_pixel(
+        &self,
+        origin: PointI32,
+        apply_config: &ApplyConfig,
+        mut set_pixel: impl FnMut(i32, i32, bool),
+    ) {
+        for i in 0..self.height {
+            for j in 0..self.width {
+                let (x, y) = (origin.x + j as i32, origin.y + i as i32);
+                match apply_config.applied_pixel(self[i][j]) {
+                    AppliedPixel::Foreground => set_pixel(x, y, true),
+                    AppliedPixel::Background => set_pixel(x, y, false),
+                    AppliedPixel::Unchanged => {}
+                }
+            }
+        }
+    }
+}
+
+/// The fraction of each pixel in a hole covered by the foreground, in [0.0, 1.0]
+pub struct CoverageMatrix {
+    pub width: usize,
+    pub height: usize,
+    pub values: Vec<f64>,
+}
+
+impl CoverageMatrix {
+    /// Average the coverage of each 'factor' x 'factor' block of 'filled_hole', which is a hole filled at 'factor' times
+    /// the resolution. Texture covers a subpixel. Structure (on the boundary of the filled regions) covers the fraction
+    /// of its 4-neighbors other than Structure that are Texture, or half of the subpixel if there are none.
+    /// The behavior is undefined unless 'factor' is positive.
+    pub fn from_filled_hole(filled_hole: &FilledHoleMatrix, factor: usize) -> Self {
+        Self::average_blocks(filled_hole, factor, |i, j| match filled_hole[i][j] {
+            FilledHoleElement::Blank => 0.0,
+            FilledHoleElement::Structure => Self::estimate_structure_coverage(filled_hole, i, j),
+            FilledHoleElement::Texture => 1.0,
+        })
+    }
+
+    /// Same as 'from_filled_hole', but Structure covers a whole subpixel, as in a hole completed by symmetry
+    /// (see 'SymmetryCompletor'), where Structure is a reflected contour pixel of the foreground.
+    /// The behavior is undefined unless 'factor' is positive.
+    pub fn from_reflected_hole(filled_hole: &FilledHoleMatrix, factor: usize) -> Self {
+        Self::average_blocks(filled_hole, factor, |i, j| match filled_hole[i][j] {
+            FilledHoleElement::Blank => 0.0,
+            FilledHoleElement::Structure | FilledHoleElement::Texture => 1.0,
+        })
+    }
+
+    /// Composite the hole into the grayscale image 'pixels', stored row by row with 'image_width' pixels per row,
+    /// with the top-left corner of the hole at 'origin'.
+    /// Each pixel is blended between 'background' and 'foreground' by its coverage.
+    /// Pixels outside the image are skipped.
+    pub fn composite_into_grayscale(
+        &self,
+        pixels: &mut [u8],
+        image_width: usize,
+        origin: PointI32,
+        foreground: u8,
+        background: u8,
+    ) {
+        if image_width == 0 {
+            return;
+        }
+        let image_height = pixels.len() / image_width;
+        for i in 0..self.height {
+            for j in 0..self.width {
+                let (x, y) = (origin.x + j as i32, origin.y + i as i32);
+                if x < 0 || x >= image_width as i32 || y < 0 || y >= image_height as i32 {
+                    continue;
+                }
+                let coverage = self[i][j];
+                pixels[y as usize * image_width + x as usize] =
+                    (background as f64 * (1.0 - coverage) + foreground as f64 * coverage).round()
+                        as u8;
+            }
+        }
+    }
+}
+
+// Helper functions
+impl CoverageMatrix {
+    /// Average 'coverage_at' (row, column) of the subpixels over each 'factor' x 'factor' block of 'filled_hole'.
+    fn average_blocks<F>(filled_hole: &FilledHoleMatrix, factor: usize, coverage_at: F) -> Self
+    where
+        F: Fn(usize, usize) -> f64,
+    {
+        let (width, height) = (filled_hole.width / factor, filled_hole.height / factor);
+        let values = (0..height)
+            .flat_map(|i| (0..width).map(move |j| (i, j)))
+            .map(|(i, j)| {
+                let sum: f64 = (0..factor)
+                    .flat_map(|di| (0..factor).map(move |dj| (di, dj)))
+                    .map(|(di, dj)| coverage_at(i * factor + di, j * factor + dj))
+                    .sum();
+                sum / (factor * factor) as f64
+            })
+            .collect();
+
+        Self {
+            width,
+            height,
+            values,
+        }
+    }
+
+    /// The fraction of the 4-neighbors of the subpixel at row 'i' and column 'j' other than Structure that are
+    /// Texture, or 0.5 if there are none. Neighbors outside 'filled_hole' are skipped.
+    fn estimate_structure_coverage(filled_hole: &FilledHoleMatrix, i: usize, j: usize) -> f64 {
+        let (num_texture, num_classified) = four_neighbors(PointI32::new(j as i32, i as i32))
+            .filter(|neighbor| {
+                0 <= neighbor.x
+                    && neighbor.x < filled_hole.width as i32
+                    && 0 <= neighbor.y
+                    && neighbor.y < filled_hole.height as i32
+            })
+            .map(|neighbor| filled_hole[neighbor.y as usize][neighbor.x as usize])
+            .fold(
+                (0, 0),
+                |(num_texture, num_classified), element| match element {
+                    FilledHoleElement::Blank => (num_texture, num_classified + 1),
+                    FilledHoleElement::Structure => (num_texture, num_classified),
+                    FilledHoleElement::Texture => (num_texture + 1, num_classified + 1),
+                },
+            );
+        if num_classified == 0 {
+            0.5
+        } else {
+            num_texture as f64 / num_classified as f64
+        }
+    }
+}
+
+impl Index<usize> for CoverageMatrix {
+    type Output = [f64]; // Output a row for further indexing
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.values[(index * self.width)..((index + 1) * self.width)]
+    }
 }
 
 impl Index<usize> for FilledHoleMatrix {
@@ -107,10 +385,203 @@ impl HoleFiller {
             blank_broundary_pixels_threshold,
         )
     }
+
+    /// Same as 'fill', but at 'supersampling' times the resolution, for anti-aliasing:
+    /// the curves are rasterized and the regions are filled on 'image' upscaled by 'supersampling' around the hole.
+    /// The returned matrix is 'supersampling' times as wide and as high as 'hole_rect'.
+    /// The behavior is undefined unless 'supersampling' is positive.
+    pub fn fill_supersampled(
+        image: &BinaryImage,
+        hole_rect: BoundingRect,
+        intrapolated_curves: Vec<CompoundPath>,
+        endpoints: Vec<PointI32>,
+        blank_broundary_pixels_threshold: usize,
+        supersampling: usize,
+    ) -> Result<FilledHoleMatrix, String> {
+        if supersampling == 1 {
+            return Self::fill(
+                image,
+                hole_rect,
+                intrapolated_curves,
+                endpoints,
+                blank_broundary_pixels_threshold,
+            );
+        }
+
+        // Only the hole and the pixels around it are upscaled
+        let factor = supersampling as i32;
+        let origin = PointI32::new(hole_rect.left - 1, hole_rect.top - 1);
+        let (width, height) = (
+            (hole_rect.width() + 2) * factor,
+            (hole_rect.height() + 2) * factor,
+        );
+        let mut upscaled_image = BinaryImage::new_w_h(width as usize, height as usize);
+        for y in 0..height {
+            for x in 0..width {
+                upscaled_image.set_pixel(
+                    x as usize,
+                    y as usize,
+                    image.get_pixel_safe(origin.x + x / factor, origin.y + y / factor),
+                );
+            }
+        }
+
+        let upscaled_curves = Self::upscale_curves(intrapolated_curves, origin, factor);
+        let upscaled_endpoints = endpoints
+            .into_iter()
+            .map(|endpoint| {
+                PointI32::new(
+                    (endpoint.x - origin.x) * factor,
+                    (endpoint.y - origin.y) * factor,
+                )
+            })
+            .collect();
+
+        Self::fill(
+            &upscaled_image,
+            BoundingRect::new_x_y_w_h(
+                factor,
+                factor,
+                hole_rect.width() * factor,
+                hole_rect.height() * factor,
+            ),
+            upscaled_curves,
+            upscaled_endpoints,
+            blank_broundary_pixels_threshold * supersampling,
+        )
+    }
+
+    /// Return a FilledHoleMatrix representing what is inside the hole after filling, at 'supersampling' times the
+    /// resolution, where a pixel is filled if its center is inside an odd number of the loops formed by
+    /// 'visible_contours' and 'intrapolated_curves'.
+    /// Each of 'visible_contours' is either closed (its first and last points are the same)
+    /// or has both ends on the hole boundary. Each end of the intrapolated curves is joined to the closest end
+    /// of the open visible contours, so no pixel is classified by sampling the image.
+    /// The behavior is undefined unless 'supersampling' is positive.
+    pub fn fill_by_even_odd(
+        hole_rect: BoundingRect,
+        intrapolated_curves: Vec<CompoundPath>,
+        visible_contours: &[PathI32],
+        supersampling: usize,
+    ) -> FilledHoleMatrix {
+        let factor = supersampling as i32;
+        let origin = PointI32::new(hole_rect.left, hole_rect.top);
+        let to_matrix = |point: PointF64| (point - origin.to_point_f64()) * factor as f64;
+
+        // The loops as line segments in the coordinates of the matrix
+        let mut edges: Vec<(PointF64, PointF64)> = visible_contours
+            .iter()
+            .flat_map(|contour| {
+                contour.path.windows(2).map(|points| {
+                    (
+                        to_matrix(points[0].to_point_f64()),
+                        to_matrix(points[1].to_point_f64()),
+                    )
+                })
+            })
+            .collect();
+        let contour_ends: Vec<PointF64> = visible_contours
+            .iter()
+            .filter(|contour| contour.len() > 1 && contour[0] != contour[contour.len() - 1])
+            .flat_map(|contour| [contour[0], contour[contour.len() - 1]])
+            .map(|end| to_matrix(end.to_point_f64()))
+            .collect();
+        intrapolated_curves.iter().for_each(|compound_path| {
+            let points: Vec<PointF64> = sample_compound_path(compound_path, CURVE_FLATTENING_STEP)
+                .into_iter()
+                .map(to_matrix)
+                .collect();
+            if points.is_empty() {
+                return;
+            }
+            edges.extend(points.windows(2).map(|points| (points[0], points[1])));
+            IntoIterator::into_iter([points[0], points[points.len() - 1]]).for_each(|curve_end| {
+                if let Some(&contour_end) = contour_ends.iter().min_by(|a, b| {
+                    a.distance_to(curve_end)
+                        .total_cmp(&b.distance_to(curve_end))
+                }) {
+                    edges.push((curve_end, contour_end));
+                }
+            });
+        });
+
+        let matrix = FilledHoleMatrix::new(
+            hole_rect.width() as usize * supersampling,
+            hole_rect.height() as usize * supersampling,
+        );
+        let mut matrix = Self::rasterize_intrapolated_curves(
+            matrix,
+            Self::upscale_curves(intrapolated_curves, origin, factor),
+            PointI32::new(0, 0),
+        );
+
+        for i in 0..matrix.height {
+            let y = i as f64 + 0.5;
+            // Where the row crosses the loops, counting an edge only if it has an end on each side of the row
+            let mut crossings: Vec<f64> = edges
+                .iter()
+                .filter(|(from, to)| (from.y <= y) != (to.y <= y))
+                .map(|(from, to)| from.x + (to.x - from.x) * (y - from.y) / (to.y - from.y))
+                .collect();
+            crossings.sort_by(|a, b| a.total_cmp(b));
+
+            let mut num_crossings_on_left = 0;
+            for j in 0..matrix.width {
+                let x = j as f64 + 0.5;
+                while num_crossings_on_left < crossings.len()
+                    && crossings[num_crossings_on_left] < x
+                {
+                    num_crossings_on_left += 1;
+                }
+                if (crossings.len() - num_crossings_on_left) % 2 == 1
+                    && matrix[i][j] == FilledHoleElement::Blank
+                {
+                    matrix[i][j] = FilledHoleElement::Texture;
+                }
+            }
+        }
+
+        matrix
+    }
 }
 
 // Helper functions
 impl HoleFiller {
+    /// Transform 'curves' so that 'origin' is moved to (0, 0) and everything is scaled by 'factor'.
+    fn upscale_curves(
+        curves: Vec<CompoundPath>,
+        origin: PointI32,
+        factor: i32,
+    ) -> Vec<CompoundPath> {
+        let upscale = |point: PointF64| (point - origin.to_point_f64()) * factor as f64;
+        curves
+            .into_iter()
+            .map(|mut compound_path| {
+                compound_path
+                    .iter_mut()
+                    .for_each(|path_elem| match path_elem {
+                        visioniechor::CompoundPathElement::PathI32(path) => {
+                            path.path.iter_mut().for_each(|point| {
+                                *point = PointI32::new(
+                                    (point.x - origin.x) * factor,
+                                    (point.y - origin.y) * factor,
+                                )
+                            })
+                        }
+                        visioniechor::CompoundPathElement::PathF64(path) => path
+                            .path
+                            .iter_mut()
+                            .for_each(|point| *point = upscale(*point)),
+                        visioniechor::CompoundPathElement::Spline(spline) => spline
+                            .points
+                            .iter_mut()
+                            .for_each(|point| *point = upscale(*point)),
+                    });
+                compound_path
+            })
+            .collect()
+    }
+
     fn rasterize_intrapolated_curves(
         mut matrix: FilledHoleMatrix,
         curves: Vec<CompoundPath>,
@@ -130,9 +601,8 @@ impl HoleFiller {
                     }
                     visioniechor::CompoundPathElement::PathF64(path) => {
                         path.offset(&offset.to_point_f64());
-                        path.iter().for_each(|point| {
-                            let point = PointUsize::new(point.x as usize, point.y as usize);
-                            matrix[point] = FilledHoleElement::Structure;
+                        path.path.windows(2).for_each(|points| {
+                            Self::rasterize_line(&mut matrix, points[0], points[1]);
                         })
                     }
                     visioniechor::CompoundPathElement::Spline(spline) => {
@@ -173,6 +643,20 @@ impl HoleFiller {
         }
     }
 
+    fn rasterize_line(matrix: &mut FilledHoleMatrix, from: PointF64, to: PointF64) {
+        let quantization_levels = std::cmp::max((from.distance_to(to) as usize) << 2, 1);
+
+        for i in 0..=quantization_levels {
+            let t = i as f64 / quantization_levels as f64;
+            let p = from + (to - from) * t;
+            // Points outside the matrix are skipped rather than clamped onto its edges
+            if p.x < 0.0 || p.y < 0.0 || p.x >= matrix.width as f64 || p.y >= matrix.height as f64 {
+                continue;
+            }
+            matrix[PointUsize::new(p.x as usize, p.y as usize)] = FilledHoleElement::Structure;
+        }
+    }
+
     /// The behavior is undefined unless 'offset' is the top-left corner of 'hole_rect' (exactly on its boundary).
     fn fill_holes(
         mut matrix: FilledHoleMatrix,
@@ -314,38 +798,349 @@ impl HoleFiller {
 
     /// Flood fill a region of FilledHoleElement::Blank starting at 'seed' in an iterative manner.
     fn fill_hole_iterative(matrix: &mut FilledHoleMatrix, seed: PointI32) {
-        let mut stack = vec![seed];
-        while !stack.is_empty() {
-            let point = stack.pop().unwrap();
-
-            // Out of range
-            if point.x < 0
-                || point.x >= matrix.width as i32
-                || point.y < 0
-                || point.y >= matrix.height as i32
-            {
-                continue;
-            }
-
+        let (width, height) = (matrix.width, matrix.height);
+        flood_fill_4_connected(seed, width, height, |point| {
             let point_usize = point.to_point_usize();
-
             // Already filled
             if matrix[point_usize] != FilledHoleElement::Blank {
-                continue;
+                return false;
             }
-
-            // Fill it
             matrix[point_usize] = FilledHoleElement::Texture;
+            true
+        });
+    }
+}
 
-            IntoIterator::into_iter([
-                point + PointI32::new(1, 0),
-                point + PointI32::new(0, 1),
-                point + PointI32::new(-1, 0),
-                point + PointI32::new(0, -1),
-            ])
-            .for_each(|neighbor| {
-                stack.push(neighbor);
-            });
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use visioniechor::PathF64;
+
+    #[test]
+    fn coverage_is_fractional_along_diagonal_boundary() {
+        // GIVEN
+        // A hole filled at 4 times the resolution, below a diagonal boundary
+        let mut filled_hole = FilledHoleMatrix::new(8, 8);
+        for i in 0..8 {
+            for j in 0..8 {
+                filled_hole[i][j] = match i.cmp(&j) {
+                    std::cmp::Ordering::Less => FilledHoleElement::Blank,
+                    std::cmp::Ordering::Equal => FilledHoleElement::Structure,
+                    std::cmp::Ordering::Greater => FilledHoleElement::Texture,
+                };
+            }
+        }
+
+        // WHEN
+        let coverage = CoverageMatrix::from_filled_hole(&filled_hole, 4);
+
+        // THEN
+        // A block on the diagonal has 6 Texture and 4 Structure subpixels out of 16
+        assert_eq!((coverage.width, coverage.height), (2, 2));
+        assert_eq!(coverage[0][0], 0.5);
+        assert_eq!(coverage[1][1], 0.5);
+        assert_eq!(coverage[0][1], 0.0);
+        assert_eq!(coverage[1][0], 1.0);
+    }
+
+    #[test]
+    fn structure_coverage_follows_neighboring_subpixels() {
+        // GIVEN
+        // Two blocks at 4 times the resolution, each with a column of Structure,
+        // inside Texture in the left block and inside Blank in the right block
+        let mut filled_hole = FilledHoleMatrix::new(8, 4);
+        for i in 0..4 {
+            for j in 0..8 {
+                filled_hole[i][j] = match j {
+                    1 | 5 => FilledHoleElement::Structure,
+                    0..=3 => FilledHoleElement::Texture,
+                    _ => FilledHoleElement::Blank,
+                };
+            }
+        }
+
+        // WHEN
+        let coverage = CoverageMatrix::from_filled_hole(&filled_hole, 4);
+        let reflected_coverage = CoverageMatrix::from_reflected_hole(&filled_hole, 4);
+
+        // THEN
+        assert_eq!(coverage[0][0], 1.0);
+        assert_eq!(coverage[0][1], 0.0);
+        assert_eq!(reflected_coverage[0][0], 1.0);
+        assert_eq!(reflected_coverage[0][1], 0.25);
+    }
+
+    #[test]
+    fn apply_config_maps_each_element() {
+        // GIVEN
+        let apply_config = ApplyConfig {
+            blank: AppliedPixel::Unchanged,
+            structure: AppliedPixel::Background,
+            texture: AppliedPixel::Foreground,
+        };
+
+        // THEN
+        assert_eq!(
+            apply_config.applied_pixel(FilledHoleElement::Blank),
+            AppliedPixel::Unchanged
+        );
+        assert_eq!(
+            apply_config.applied_pixel(FilledHoleElement::Structure),
+            AppliedPixel::Background
+        );
+        assert_eq!(
+            apply_config.applied_pixel(FilledHoleElement::Texture),
+            AppliedPixel::Foreground
+        );
+        let default_config = ApplyConfig::default();
+        assert_eq!(
+            default_config.applied_pixel(FilledHoleElement::Blank),
+            AppliedPixel::Background
+        );
+        assert_eq!(
+            default_config.applied_pixel(FilledHoleElement::Structure),
+            AppliedPixel::Foreground
+        );
+        assert_eq!(
+            default_config.applied_pixel(FilledHoleElement::Texture),
+            AppliedPixel::Foreground
+        );
+    }
+
+    /// A 3 x 2 hole with the columns Blank, Structure and Texture, and the config keeping Blank pixels,
+    /// clearing Structure pixels and setting Texture pixels
+    fn striped_hole() -> (FilledHoleMatrix, ApplyConfig) {
+        let mut filled_hole = FilledHoleMatrix::new(3, 2);
+        for i in 0..2 {
+            filled_hole[i][1] = FilledHoleElement::Structure;
+            filled_hole[i][2] = FilledHoleElement::Texture;
+        }
+        let apply_config = ApplyConfig {
+            blank: AppliedPixel::Unchanged,
+            structure: AppliedPixel::Background,
+            texture: AppliedPixel::Foreground,
+        };
+        (filled_hole, apply_config)
+    }
+
+    #[test]
+    fn apply_to_binary_image_in_place_or_on_copy() {
+        // GIVEN
+        // A checkerboard, with the hole at (1, 1)
+        let (filled_hole, apply_config) = striped_hole();
+        let mut image = BinaryImage::new_w_h(5, 4);
+        for y in 0..4 {
+            for x in 0..5 {
+                image.set_pixel(x, y, (x + y) % 2 == 0);
+            }
+        }
+        let original = image.clone();
+        let origin = PointI32::new(1, 1);
+
+        // WHEN
+        let copy = filled_hole.applied_to_binary_image(&image, origin, &apply_config);
+        let copy_is_independent = image.get_pixel(2, 2) == original.get_pixel(2, 2);
+        filled_hole.apply_to_binary_image(&mut image, origin, &apply_config);
+
+        // THEN
+        assert!(copy_is_independent);
+        for y in 0..4 {
+            for x in 0..5 {
+                let expected = match (x, y) {
+                    (2, 1..=2) => false,
+                    (3, 1..=2) => true,
+                    _ => original.get_pixel(x, y),
+                };
+                assert_eq!(copy.get_pixel(x, y), expected, "({}, {})", x, y);
+                assert_eq!(image.get_pixel(x, y), expected, "({}, {})", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn apply_to_color_image_in_place_or_on_copy() {
+        // GIVEN
+        let (filled_hole, apply_config) = striped_hole();
+        let (gray, black, white) = (
+            Color::new(128, 128, 128),
+            Color::new(0, 0, 0),
+            Color::new(255, 255, 255),
+        );
+        let mut image = ColorImage::new_w_h(5, 4);
+        for y in 0..4 {
+            for x in 0..5 {
+                image.set_pixel(x, y, &gray);
+            }
+        }
+        let origin = PointI32::new(1, 1);
+
+        // WHEN
+        let copy = filled_hole.applied_to_color_image(&image, origin, &apply_config, black, white);
+        let copy_is_independent = image.get_pixel(3, 1) == gray;
+        filled_hole.apply_to_color_image(&mut image, origin, &apply_config, black, white);
+
+        // THEN
+        assert!(copy_is_independent);
+        for y in 0..4 {
+            for x in 0..5 {
+                let expected = match (x, y) {
+                    (2, 1..=2) => white,
+                    (3, 1..=2) => black,
+                    _ => gray,
+                };
+                assert!(copy.get_pixel(x, y) == expected, "({}, {})", x, y);
+                assert!(image.get_pixel(x, y) == expected, "({}, {})", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn apply_skips_hole_pixels_outside_image() {
+        // GIVEN
+        // The hole sticks out of the bottom-right corner of the image, and out of the top-left corner
+        let (filled_hole, apply_config) = striped_hole();
+        let (binary_image, color_image) = (BinaryImage::new_w_h(4, 4), ColorImage::new_w_h(4, 4));
+        let (black, white) = (Color::new(0, 0, 0), Color::new(255, 255, 255));
+
+        for &origin in [PointI32::new(2, 3), PointI32::new(-2, -1)].iter() {
+            // WHEN
+            let binary_image =
+                filled_hole.applied_to_binary_image(&binary_image, origin, &apply_config);
+            let color_image = filled_hole.applied_to_color_image(
+                &color_image,
+                origin,
+                &apply_config,
+                black,
+                white,
+            );
+
+            // THEN
+            for y in 0..4 {
+                for x in 0..4 {
+                    let is_texture =
+                        x as i32 == origin.x + 2 && (origin.y..origin.y + 2).contains(&(y as i32));
+                    assert_eq!(binary_image.get_pixel(x, y), is_texture);
+                    assert!((color_image.get_pixel(x, y) == black) == is_texture);
+                }
+            }
+        }
+    }
+
+    /// The visible contours and intrapolated curves of horizontal bands (top and bottom y) crossing the hole
+    /// (10, 10) to (30, 30)
+    fn bands_across_hole(bands: &[(i32, i32)]) -> (Vec<PathI32>, Vec<CompoundPath>) {
+        let path_i32 = |points: &[(i32, i32)]| {
+            PathI32::from_points(points.iter().map(|&(x, y)| PointI32::new(x, y)).collect())
+        };
+        let line = |y: i32| {
+            let mut compound_path = CompoundPath::new();
+            compound_path.add_path_f64(PathF64::from_points(vec![
+                PointF64::new(10.0, y as f64),
+                PointF64::new(30.0, y as f64),
+            ]));
+            compound_path
+        };
+        let contours = bands
+            .iter()
+            .flat_map(|&(top, bottom)| {
+                [
+                    path_i32(&[(10, top), (0, top), (0, bottom), (10, bottom)]),
+                    path_i32(&[(30, top), (40, top), (40, bottom), (30, bottom)]),
+                ]
+            })
+            .collect();
+        let curves = bands
+            .iter()
+            .flat_map(|&(top, bottom)| [line(top), line(bottom)])
+            .collect();
+        (contours, curves)
+    }
+
+    #[test]
+    fn even_odd_fills_between_two_strokes_crossing_hole() {
+        // GIVEN
+        let hole_rect = BoundingRect::new_x_y_w_h(10, 10, 20, 20);
+        let (contours, curves) = bands_across_hole(&[(12, 16), (22, 26)]);
+
+        // WHEN
+        let filled_hole = HoleFiller::fill_by_even_odd(hole_rect, curves, &contours, 1);
+
+        // THEN
+        for i in 0..filled_hole.height {
+            let y = 10.0 + i as f64 + 0.5;
+            let is_inside = (12.0 < y && y < 16.0) || (22.0 < y && y < 26.0);
+            for j in 0..filled_hole.width {
+                match filled_hole[i][j] {
+                    FilledHoleElement::Structure => {}
+                    FilledHoleElement::Texture => assert!(is_inside),
+                    FilledHoleElement::Blank => assert!(!is_inside),
+                }
+            }
+        }
+        assert!(filled_hole.elems.contains(&FilledHoleElement::Texture));
+    }
+
+    #[test]
+    fn even_odd_leaves_nested_contour_blank() {
+        // GIVEN
+        // A stroke crossing the hole, with a closed contour of a blank island inside it
+        let hole_rect = BoundingRect::new_x_y_w_h(10, 10, 20, 20);
+        let (mut contours, curves) = bands_across_hole(&[(12, 28)]);
+        contours.push(PathI32::from_points(vec![
+            PointI32::new(16, 16),
+            PointI32::new(24, 16),
+            PointI32::new(24, 24),
+            PointI32::new(16, 24),
+            PointI32::new(16, 16),
+        ]));
+
+        // WHEN
+        let filled_hole = HoleFiller::fill_by_even_odd(hole_rect, curves, &contours, 1);
+
+        // THEN
+        for i in 0..filled_hole.height {
+            for j in 0..filled_hole.width {
+                let (x, y) = (10.0 + j as f64 + 0.5, 10.0 + i as f64 + 0.5);
+                let is_in_island = 16.0 < x && x < 24.0 && 16.0 < y && y < 24.0;
+                let is_inside = 12.0 < y && y < 28.0 && !is_in_island;
+                match filled_hole[i][j] {
+                    FilledHoleElement::Structure => {}
+                    FilledHoleElement::Texture => assert!(is_inside),
+                    FilledHoleElement::Blank => assert!(!is_inside),
+                }
+            }
+        }
+        assert!(filled_hole[10][10] == FilledHoleElement::Blank);
+    }
+
+    #[test]
+    fn rasterize_line_skips_points_outside_matrix() {
+        // GIVEN
+        let mut matrix = FilledHoleMatrix::new(4, 4);
+
+        // WHEN
+        // A line across the matrix and beyond both sides, and a line just below it
+        HoleFiller::rasterize_line(
+            &mut matrix,
+            PointF64::new(-2.0, 1.5),
+            PointF64::new(6.0, 1.5),
+        );
+        HoleFiller::rasterize_line(
+            &mut matrix,
+            PointF64::new(0.5, 4.5),
+            PointF64::new(3.5, 5.0),
+        );
+
+        // THEN
+        for i in 0..4 {
+            for j in 0..4 {
+                let expected = if i == 1 {
+                    FilledHoleElement::Structure
+                } else {
+                    FilledHoleElement::Blank
+                };
+                assert!(matrix[i][j] == expected);
+            }
         }
     }
 }
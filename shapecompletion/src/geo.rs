@@ -2,14 +2,25 @@ use std::f64::consts::PI;
 
 use flo_curves::{
     bezier::{curve_intersects_curve_clip, Curve},
-    Coord2, Coordinate,
+    BezierCurve, Coord2, Coordinate2D,
 };
-use visioniechor::{CompoundPath, CompoundPathElement, PathF64, PointF64, Spline};
+use visioniechor::{CompoundPath, CompoundPathElement, PathF64, PathI32, PointF64, Spline};
 
 // Geometry helper functions
 
-fn f64_approximately(a: f64, b: f64) -> bool {
-    (a - b).abs() <= f64::EPSILON
+/// Determine if 'value' is zero up to rounding errors, where 'scale' is the magnitude of the terms 'value' is computed from.
+fn is_negligible(value: f64, scale: f64) -> bool {
+    value.abs() <= 4.0 * f64::EPSILON * scale
+}
+
+/// Determine if the directions 'a' and 'b' (in the same or opposite senses) differ by at most 'angular_tolerance'
+/// radians, or by a negligible angle if 'angular_tolerance' is 0.0.
+/// The result does not depend on the lengths of 'a' and 'b'.
+/// The behavior is undefined unless 0.0 <= angular_tolerance < pi/2.
+pub(super) fn are_directions_parallel(a: PointF64, b: PointF64, angular_tolerance: f64) -> bool {
+    let scale = a.norm() * b.norm();
+    let cross = cross_product(a, b);
+    is_negligible(cross, scale) || cross.abs() <= scale * angular_tolerance.sin()
 }
 
 /// ratio : returned point
@@ -43,12 +54,16 @@ pub(super) enum LineIntersectionResult {
     None,                // No intersection in the positive directions
 }
 
-// Given directed lines p1p2 and p3p4, returns their intersection result.
+/// Given directed lines p1p2 and p3p4, returns their intersection result.
+/// The lines are parallel if their directions differ by at most 'parallel_tolerance' radians (see 'are_directions_parallel'),
+/// and they are coincident if, in addition, the direction from p3 to p1 is parallel to them.
+/// The result does not depend on the scale of the coordinates.
 pub(super) fn calculate_intersection(
     p1: PointF64,
     p2: PointF64,
     p3: PointF64,
     p4: PointF64,
+    parallel_tolerance: f64,
 ) -> LineIntersectionResult {
     let extract_coords = |p: &PointF64| (p.x, p.y);
     let (x1, y1) = extract_coords(&p1);
@@ -61,14 +76,12 @@ pub(super) fn calculate_intersection(
     let denom = (y4 - y3) * (x2 - x1) - (x4 - x3) * (y2 - y1);
     let numera_a = (x4 - x3) * (y1 - y3) - (y4 - y3) * (x1 - x3);
     let numera_b = (x2 - x1) * (y1 - y3) - (y2 - y1) * (x1 - x3);
-    if f64_approximately(denom, 0.0)
-        && f64_approximately(numera_a, 0.0)
-        && f64_approximately(numera_b, 0.0)
-    {
-        return LineIntersectionResult::Coincidence;
-    }
-    if f64_approximately(denom, 0.0) {
-        return LineIntersectionResult::Parallel;
+    if are_directions_parallel(p2 - p1, p4 - p3, parallel_tolerance) {
+        return if are_directions_parallel(p2 - p1, p1 - p3, parallel_tolerance) {
+            LineIntersectionResult::Coincidence
+        } else {
+            LineIntersectionResult::Parallel
+        };
     }
     let u_a = numera_a / denom;
     let u_b = numera_b / denom;
@@ -107,6 +120,84 @@ pub(super) fn signed_angle_difference(from: &f64, to: &f64) -> f64 {
     }
 }
 
+/// The z-component of the cross product of 'a' and 'b'.
+/// Positive if 'b' is in the direction of positive angles from 'a'.
+pub(super) fn cross_product(a: PointF64, b: PointF64) -> f64 {
+    a.x * b.y - a.y * b.x
+}
+
+/// Find the signed angle in (-pi, pi] to rotate 'from' to the direction of 'to'.
+pub(super) fn signed_angle_between(from: PointF64, to: PointF64) -> f64 {
+    cross_product(from, to).atan2(from.dot(to))
+}
+
+/// Rotate 'v' by 'angle' radians in the direction of positive angles.
+pub(super) fn rotate_vector(v: PointF64, angle: f64) -> PointF64 {
+    let (sin, cos) = angle.sin_cos();
+    PointF64::new(v.x * cos - v.y * sin, v.x * sin + v.y * cos)
+}
+
+/// Normalize an angle into (-pi, pi].
+pub(super) fn normalize_angle(angle: f64) -> f64 {
+    let mut angle = angle % (2.0 * PI);
+    if angle > PI {
+        angle -= 2.0 * PI;
+    } else if angle <= -PI {
+        angle += 2.0 * PI;
+    }
+    angle
+}
+
+/// Fit the polynomial of degree 'degree' to the samples ('xs[i]', 'ys[i]') by least squares.
+/// Return its coefficients in increasing order of power, or None if the normal equations are singular.
+/// The behavior is undefined unless 'xs' and 'ys' have the same length.
+pub(super) fn fit_polynomial_least_squares(
+    xs: &[f64],
+    ys: &[f64],
+    degree: usize,
+) -> Option<Vec<f64>> {
+    let n = degree + 1;
+
+    // Normal equations as an augmented matrix
+    let mut matrix = vec![vec![0.0; n + 1]; n];
+    for (&x, &y) in xs.iter().zip(ys.iter()) {
+        let powers: Vec<f64> = (0..(2 * n)).map(|i| x.powi(i as i32)).collect();
+        for (i, row) in matrix.iter_mut().enumerate() {
+            for (j, elem) in row.iter_mut().take(n).enumerate() {
+                *elem += powers[i + j];
+            }
+            row[n] += powers[i] * y;
+        }
+    }
+
+    // Gaussian elimination with partial pivoting
+    for col in 0..n {
+        let pivot =
+            (col..n).max_by(|&i, &j| matrix[i][col].abs().total_cmp(&matrix[j][col].abs()))?;
+        if matrix[pivot][col].abs() < f64::EPSILON {
+            return None;
+        }
+        matrix.swap(col, pivot);
+        let (upper_rows, lower_rows) = matrix.split_at_mut(col + 1);
+        let pivot_row = &upper_rows[col];
+        for row in lower_rows.iter_mut() {
+            let factor = row[col] / pivot_row[col];
+            row.iter_mut()
+                .zip(pivot_row.iter())
+                .skip(col)
+                .for_each(|(elem, pivot_elem)| *elem -= factor * pivot_elem);
+        }
+    }
+
+    // Back substitution
+    let mut coeffs = vec![0.0; n];
+    for row in (0..n).rev() {
+        let sum: f64 = ((row + 1)..n).map(|k| matrix[row][k] * coeffs[k]).sum();
+        coeffs[row] = (matrix[row][n] - sum) / matrix[row][row];
+    }
+    Some(coeffs)
+}
+
 /// Takes a path representing an arbitrary curve, returns a vector of bool representing its corners
 /// (angle in radians bigger than or equal to 'threshold').
 /// `path` is considered to be open (not forming a closed shape);
@@ -139,6 +230,73 @@ pub(super) fn find_corners_open_path(path: &PathF64, threshold: f64) -> Vec<bool
     corners
 }
 
+/// Same as 'find_corners_open_path' if 'scale' is not positive.
+/// Otherwise, the angle at each point is measured between the directions from the point 'scale' before it
+/// and to the point 'scale' after it along the path (clipped at the ends), so that rounded corners spread over several
+/// points are detected, and noise shorter than 'scale' is not. Among points with an angle of at least 'threshold',
+/// only those with the biggest angle within 'scale' along the path are corners.
+pub(super) fn find_corners_open_path_at_scale(
+    path: &PathF64,
+    threshold: f64,
+    scale: f64,
+) -> Vec<bool> {
+    if scale <= 0.0 || path.is_empty() {
+        return find_corners_open_path(path, threshold);
+    }
+
+    let path = path.to_open();
+    let len = path.len();
+
+    // Arc length from the first point to each point
+    let arc_lengths: Vec<f64> = std::iter::once(0.0)
+        .chain(path.path.windows(2).scan(0.0, |arc_length, points| {
+            *arc_length += points[0].distance_to(points[1]);
+            Some(*arc_length)
+        }))
+        .collect();
+    let point_at_arc_length = |arc_length: f64| {
+        let arc_length = arc_length.max(0.0).min(arc_lengths[len - 1]);
+        let i = arc_lengths
+            .windows(2)
+            .position(|pair| arc_length <= pair[1])
+            .unwrap_or(0);
+        let segment_length = arc_lengths[i + 1] - arc_lengths[i];
+        if segment_length < f64::EPSILON {
+            path[i]
+        } else {
+            calculate_in_between_point(
+                path[i],
+                path[i + 1],
+                (arc_length - arc_lengths[i]) / segment_length,
+            )
+        }
+    };
+
+    let angles: Vec<f64> = (0..len)
+        .map(|i| {
+            if i == 0 || i == len - 1 {
+                return 0.0;
+            }
+            let v1 = path[i] - point_at_arc_length(arc_lengths[i] - scale);
+            let v2 = point_at_arc_length(arc_lengths[i] + scale) - path[i];
+            if v1.norm() < f64::EPSILON || v2.norm() < f64::EPSILON {
+                0.0
+            } else {
+                signed_angle_between(v1, v2).abs()
+            }
+        })
+        .collect();
+
+    (0..len)
+        .map(|i| {
+            angles[i] >= threshold
+                && (0..len)
+                    .filter(|&j| (arc_lengths[j] - arc_lengths[i]).abs() <= scale)
+                    .all(|j| angles[j] < angles[i] || (angles[j] == angles[i] && j >= i))
+        })
+        .collect()
+}
+
 /// Finds mid-points between (p_i and p_j) and (p_1 and p_2), where p_i and p_j should be between p_1 and p_2,
 /// then returns the new point constructed by the 4-point scheme
 pub(super) fn find_new_point_from_4_point_scheme(
@@ -162,67 +320,355 @@ pub(super) fn find_new_point_from_4_point_scheme(
     mid_out + vector_out.get_normalized() * new_magnitude
 }
 
-/// Determine if any curves in one of the compound paths intersect with another curve in another compound path.
-/// Assume that no curves within any single compound path intersect with each other.
-/// The behavior is undefined unless all elements in all compound paths are Spline and contain exactly 1 curve.
-pub(super) fn bezier_curves_intersection(compound_curves: &[CompoundPath]) -> bool {
-    // Assertion
-    compound_curves.iter().for_each(|compound_curve| {
-        compound_curve.iter().for_each(|curve| {
-            if let CompoundPathElement::Spline(curve) = curve {
-                assert_eq!(curve.num_curves(), 1);
+/// Returns the point halfway (in parameter) between p_i and p_j on the centripetal Catmull-Rom spline
+/// through p_1, p_i, p_j and p_2, evaluated by the Barry and Goldman pyramid.
+/// Falls back to the uniform Catmull-Rom spline if any two consecutive points coincide.
+pub(super) fn find_new_point_from_centripetal_catmull_rom(
+    p_i: PointF64,
+    p_j: PointF64,
+    p_1: PointF64,
+    p_2: PointF64,
+) -> PointF64 {
+    let knot_interval = |from: PointF64, to: PointF64| from.distance_to(to).sqrt();
+    let (d0, d1, d2) = (
+        knot_interval(p_1, p_i),
+        knot_interval(p_i, p_j),
+        knot_interval(p_j, p_2),
+    );
+    if d0 < f64::EPSILON || d1 < f64::EPSILON || d2 < f64::EPSILON {
+        return (p_i + p_j) * (9.0 / 16.0) - (p_1 + p_2) / 16.0;
+    }
+
+    let (t0, t1, t2, t3) = (0.0, d0, d0 + d1, d0 + d1 + d2);
+    let t = (t1 + t2) / 2.0;
+    let lerp = |from: PointF64, to: PointF64, from_t: f64, to_t: f64| {
+        from * ((to_t - t) / (to_t - from_t)) + to * ((t - from_t) / (to_t - from_t))
+    };
+    let a1 = lerp(p_1, p_i, t0, t1);
+    let a2 = lerp(p_i, p_j, t1, t2);
+    let a3 = lerp(p_j, p_2, t2, t3);
+    let b1 = lerp(a1, a2, t0, t2);
+    let b2 = lerp(a2, a3, t1, t3);
+    lerp(b1, b2, t1, t2)
+}
+
+/// Construct the cubic bezier curve from 'from' to 'to' with the given derivatives at the two ends.
+/// 'to_derivative' points in the direction of travel at 'to'.
+pub(super) fn hermite_to_cubic_bezier(
+    from: PointF64,
+    from_derivative: PointF64,
+    to: PointF64,
+    to_derivative: PointF64,
+) -> Spline {
+    let mut spline = Spline::new(from);
+    spline.add(from + from_derivative / 3.0, to - to_derivative / 3.0, to);
+    spline
+}
+
+/// The integral of the squared curvature over the arc length of the cubic bezier curve with control points 'points',
+/// a measure of how much the curve bends which does not depend on how the curve is parameterized.
+/// It is approximated by Simpson's rule, and points where the curve has zero speed are ignored.
+/// The behavior is undefined unless 'points' contains exactly 4 points.
+pub(super) fn calculate_bending_energy(points: &[PointF64]) -> f64 {
+    const NUM_INTERVALS: usize = 32; // Must be even for Simpson's rule
+
+    // The first derivative is 3 * ((1 - t)^2 * d0 + 2 * (1 - t) * t * d1 + t^2 * d2)
+    // and the second derivative is 6 * ((1 - t) * a + t * b)
+    let (d0, d1, d2) = (
+        points[1] - points[0],
+        points[2] - points[1],
+        points[3] - points[2],
+    );
+    let (a, b) = (d1 - d0, d2 - d1);
+    // The integrand of the energy over the parameter t, which is curvature^2 * speed
+    let integrand = |t: f64| {
+        let s = 1.0 - t;
+        let first = (d0 * (s * s) + d1 * (2.0 * s * t) + d2 * (t * t)) * 3.0;
+        let second = (a * s + b * t) * 6.0;
+        let speed_squared = first.dot(first);
+        if speed_squared < f64::EPSILON {
+            return 0.0;
+        }
+        cross_product(first, second).powi(2) / speed_squared.powf(2.5)
+    };
+
+    let h = 1.0 / NUM_INTERVALS as f64;
+    let sum: f64 = (0..=NUM_INTERVALS)
+        .map(|i| {
+            let weight = if i == 0 || i == NUM_INTERVALS {
+                1.0
+            } else if i % 2 == 1 {
+                4.0
             } else {
-                panic!("Not all compound paths are Spline.");
-            }
+                2.0
+            };
+            weight * integrand(i as f64 * h)
         })
-    });
+        .sum();
+    sum * h / 3.0
+}
+
+/// An axis-aligned bounding box
+#[derive(Clone, Copy)]
+struct BoundingBox {
+    min: PointF64,
+    max: PointF64,
+}
+
+impl BoundingBox {
+    /// The empty box, which overlaps nothing
+    fn empty() -> Self {
+        Self {
+            min: PointF64::new(f64::INFINITY, f64::INFINITY),
+            max: PointF64::new(f64::NEG_INFINITY, f64::NEG_INFINITY),
+        }
+    }
+
+    fn from_points<'a>(points: impl IntoIterator<Item = &'a Coord2>) -> Self {
+        points.into_iter().fold(Self::empty(), |bounds, point| {
+            bounds.union(Self {
+                min: PointF64::new(point.x(), point.y()),
+                max: PointF64::new(point.x(), point.y()),
+            })
+        })
+    }
 
-    let single_spline_to_curve = |spline: &Spline| {
-        let coords: Vec<Coord2> = spline
-            .points
+    fn union(self, other: Self) -> Self {
+        Self {
+            min: PointF64::new(self.min.x.min(other.min.x), self.min.y.min(other.min.y)),
+            max: PointF64::new(self.max.x.max(other.max.x), self.max.y.max(other.max.y)),
+        }
+    }
+
+    /// Determine if the two boxes overlap, or are at most 'margin' apart along both axes.
+    fn overlaps(&self, other: &Self, margin: f64) -> bool {
+        self.min.x <= other.max.x + margin
+            && other.min.x <= self.max.x + margin
+            && self.min.y <= other.max.y + margin
+            && other.min.y <= self.max.y + margin
+    }
+}
+
+/// The bezier curves making up a compound path, each with the bounding box of its control points
+/// (which contains the curve), and the bounding box of all of them.
+struct BoundedCurves {
+    curves: Vec<(Curve<Coord2>, BoundingBox)>,
+    bounds: BoundingBox,
+}
+
+impl BoundedCurves {
+    /// Every curve of a Spline is converted as it is, and every segment of a PathF64 or PathI32 is converted
+    /// to a straight bezier curve.
+    fn new(compound_path: &CompoundPath) -> Self {
+        let line_to_curve = |from: PointF64, to: PointF64| {
+            let (from, to) = (Coord2(from.x, from.y), Coord2(to.x, to.y));
+            Curve {
+                start_point: from,
+                end_point: to,
+                control_points: (
+                    from * (2.0 / 3.0) + to * (1.0 / 3.0),
+                    from * (1.0 / 3.0) + to * (2.0 / 3.0),
+                ),
+            }
+        };
+        let lines_to_curves = |points: &[PointF64]| -> Vec<Curve<Coord2>> {
+            points
+                .windows(2)
+                .map(|points| line_to_curve(points[0], points[1]))
+                .collect()
+        };
+
+        let curves: Vec<Curve<Coord2>> = compound_path
             .iter()
-            .map(|p| Coord2::from_components(&[p.x, p.y]))
+            .flat_map(|element| match element {
+                CompoundPathElement::Spline(spline) => spline
+                    .get_control_points()
+                    .into_iter()
+                    .map(|points| Curve {
+                        start_point: Coord2(points[0].x, points[0].y),
+                        end_point: Coord2(points[3].x, points[3].y),
+                        control_points: (
+                            Coord2(points[1].x, points[1].y),
+                            Coord2(points[2].x, points[2].y),
+                        ),
+                    })
+                    .collect(),
+                CompoundPathElement::PathF64(path) => lines_to_curves(&path.path),
+                CompoundPathElement::PathI32(path) => lines_to_curves(&path.to_path_f64().path),
+            })
             .collect();
-        Curve {
-            start_point: coords[0],
-            end_point: coords[3],
-            control_points: (coords[1], coords[2]),
-        }
-    };
 
-    // Convert to a type that is easier to work with
-    let curves_vec: Vec<Vec<Curve<Coord2>>> = compound_curves
+        let curves: Vec<(Curve<Coord2>, BoundingBox)> = curves
+            .into_iter()
+            .map(|curve| {
+                let bounds = BoundingBox::from_points(&[
+                    curve.start_point,
+                    curve.control_points.0,
+                    curve.control_points.1,
+                    curve.end_point,
+                ]);
+                (curve, bounds)
+            })
+            .collect();
+        let bounds = curves
+            .iter()
+            .fold(BoundingBox::empty(), |bounds, (_, curve_bounds)| {
+                bounds.union(*curve_bounds)
+            });
+
+        Self { curves, bounds }
+    }
+
+    /// Determine if any curve intersects any curve of 'other', where intersections are located to within 'tolerance'.
+    /// Only pairs of curves whose bounding boxes are within 'tolerance' of each other are tested exactly.
+    fn intersect(&self, other: &Self, tolerance: f64) -> bool {
+        self.bounds.overlaps(&other.bounds, tolerance)
+            && self.curves.iter().any(|(curve1, bounds1)| {
+                other
+                    .curves
+                    .iter()
+                    .filter(|(_, bounds2)| bounds1.overlaps(bounds2, tolerance))
+                    .any(|(curve2, _)| {
+                        !curve_intersects_curve_clip(curve1, curve2, tolerance).is_empty()
+                    })
+            })
+    }
+}
+
+/// Return points along all curves in 'compound_path', at most 'step' apart along each curve, including the endpoints.
+/// Every segment of a PathF64 or PathI32 is treated as a straight curve.
+/// The behavior is undefined unless 'step' is positive.
+pub(super) fn sample_compound_path(compound_path: &CompoundPath, step: f64) -> Vec<PointF64> {
+    BoundedCurves::new(compound_path)
+        .curves
         .iter()
-        .map(|compound_curve| {
-            compound_curve
-                .iter()
-                .filter_map(|curve| {
-                    if let CompoundPathElement::Spline(curve) = curve {
-                        Some(single_spline_to_curve(curve))
-                    } else {
-                        None
-                    }
-                })
-                .collect()
+        .flat_map(|(curve, _)| {
+            let num_steps = std::cmp::max((curve.estimate_length() / step).ceil() as usize, 1);
+            (0..=num_steps).map(move |i| {
+                let point = curve.point_at_pos(i as f64 / num_steps as f64);
+                PointF64::new(point.x(), point.y())
+            })
         })
-        .collect();
+        .collect()
+}
 
-    let two_curves_intersect = |curve1: &Curve<Coord2>, curve2: &Curve<Coord2>| {
-        let base_length = |curve: &Curve<Coord2>| curve.start_point.distance_to(&curve.end_point);
-        let accuracy = (base_length(curve1) + base_length(curve2)) * 0.25;
+/// Return the compound path tracing 'compound_path' in the opposite direction.
+pub(super) fn reverse_compound_path(compound_path: &CompoundPath) -> CompoundPath {
+    let mut reversed = CompoundPath::new();
+    compound_path
+        .iter()
+        .rev()
+        .for_each(|element| match element {
+            CompoundPathElement::Spline(spline) => reversed.add_spline(Spline {
+                points: spline.points.iter().rev().copied().collect(),
+            }),
+            CompoundPathElement::PathF64(path) => reversed.add_path_f64(PathF64::from_points(
+                path.path.iter().rev().copied().collect(),
+            )),
+            CompoundPathElement::PathI32(path) => reversed.add_path_i32(PathI32::from_points(
+                path.path.iter().rev().copied().collect(),
+            )),
+        });
+    reversed
+}
 
-        !curve_intersects_curve_clip(curve1, curve2, accuracy).is_empty()
-    };
+/// Determine if any curves in one of the compound paths intersect with another curve in another compound path,
+/// where intersections are located to within 'tolerance'.
+/// Assume that no curves within any single compound path intersect with each other.
+/// Each segment of a PathF64 or PathI32 is treated as a straight bezier curve.
+/// Pairs of compound paths and pairs of curves whose bounding boxes are apart are skipped,
+/// so that many compound paths can be checked quickly.
+pub(super) fn bezier_curves_intersection(
+    compound_curves: &[&CompoundPath],
+    tolerance: f64,
+) -> bool {
+    let bounded_curves: Vec<BoundedCurves> = compound_curves
+        .iter()
+        .map(|compound_curve| BoundedCurves::new(compound_curve))
+        .collect();
 
     // Pair-wise checking of intersection
     // If any pair intersects, return true, else false
-    curves_vec.iter().enumerate().any(|(i, curves_i)| {
-        curves_i.iter().any(|curve_i| {
-            curves_vec.iter().skip(i + 1).any(|curves_j| {
-                curves_j
-                    .iter()
-                    .any(|curve_j| two_curves_intersect(curve_i, curve_j))
-            })
+    bounded_curves.iter().enumerate().any(|(i, curves_i)| {
+        bounded_curves
+            .iter()
+            .skip(i + 1)
+            .any(|curves_j| curves_i.intersect(curves_j, tolerance))
+    })
+}
+
+/// The sampling step of 'curves_closer_than' as a ratio of the clearance it checks
+pub(super) const CLEARANCE_SAMPLING_RATIO: f64 = 0.25;
+
+/// Determine if any two of the compound paths come closer to each other than their clearance.
+/// The clearance of two compound paths is 'min_clearance', or less if all endpoints of either path are closer
+/// to the other path's endpoints, so that the two curves bounding a thin stroke need only stay as far apart as the
+/// stroke is wide. Points within the clearance from an endpoint of their own path are exempt.
+/// Distances are measured between points sampled at most 'step' apart.
+/// The behavior is undefined unless 'step' is positive.
+pub(super) fn curves_closer_than(
+    compound_curves: &[&CompoundPath],
+    min_clearance: f64,
+    step: f64,
+) -> bool {
+    let samples: Vec<Vec<PointF64>> = compound_curves
+        .iter()
+        .map(|compound_curve| sample_compound_path(compound_curve, step))
+        .collect();
+    let samples: Vec<&[PointF64]> = samples.iter().map(Vec::as_slice).collect();
+    sampled_curves_closer_than(&samples, min_clearance, step)
+}
+
+/// Same as 'curves_closer_than', but for curves already sampled at most 'step' apart (see 'sample_compound_path'),
+/// so that a curve compared many times is sampled only once.
+/// The behavior is undefined unless 'step' is positive.
+pub(super) fn sampled_curves_closer_than(
+    samples: &[&[PointF64]],
+    min_clearance: f64,
+    step: f64,
+) -> bool {
+    let endpoints = |points: &[PointF64]| -> Vec<PointF64> {
+        points
+            .first()
+            .into_iter()
+            .chain(points.last())
+            .copied()
+            .collect()
+    };
+    let distance_to_points = |point: PointF64, points: &[PointF64]| {
+        points
+            .iter()
+            .map(|other| point.distance_to(*other))
+            .fold(f64::INFINITY, f64::min)
+    };
+
+    samples.iter().enumerate().any(|(i, samples_i)| {
+        samples.iter().skip(i + 1).any(|samples_j| {
+            let (endpoints_i, endpoints_j) = (endpoints(samples_i), endpoints(samples_j));
+            let endpoints_gap = endpoints_i
+                .iter()
+                .map(|&endpoint| distance_to_points(endpoint, &endpoints_j))
+                .chain(
+                    endpoints_j
+                        .iter()
+                        .map(|&endpoint| distance_to_points(endpoint, &endpoints_i)),
+                )
+                .fold(0.0, f64::max);
+            let clearance = f64::min(min_clearance, endpoints_gap);
+            let is_constrained = |point: PointF64, endpoints: &[PointF64]| {
+                distance_to_points(point, endpoints) >= clearance
+            };
+
+            samples_i
+                .iter()
+                .filter(|&&point| is_constrained(point, &endpoints_i))
+                .any(|&point_i| {
+                    samples_j
+                        .iter()
+                        .filter(|&&point| is_constrained(point, &endpoints_j))
+                        // Allow for the sampling error
+                        .any(|&point_j| point_i.distance_to(point_j) + step < clearance)
+                })
         })
     })
 }
@@ -250,3 +696,258 @@ where
     }
     from
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PARALLEL_TOLERANCE: f64 = 1e-3;
+
+    fn intersect(p1: PointF64, p2: PointF64, p3: PointF64, p4: PointF64) -> LineIntersectionResult {
+        calculate_intersection(p1, p2, p3, p4, PARALLEL_TOLERANCE)
+    }
+
+    fn polyline(points: &[(f64, f64)]) -> CompoundPath {
+        let mut compound_path = CompoundPath::new();
+        compound_path.add_path_f64(PathF64::from_points(
+            points.iter().map(|&(x, y)| PointF64::new(x, y)).collect(),
+        ));
+        compound_path
+    }
+
+    /// A compound path of a spline through 'points', which are the control points of its consecutive curves
+    fn spline(points: &[(f64, f64)]) -> CompoundPath {
+        let mut compound_path = CompoundPath::new();
+        compound_path.add_spline(Spline {
+            points: points.iter().map(|&(x, y)| PointF64::new(x, y)).collect(),
+        });
+        compound_path
+    }
+
+    #[test]
+    fn intersection_nearly_parallel_pixel_lines_are_parallel() {
+        // GIVEN
+        // The directions differ by about 1e-4 radians, which would intersect more than 10000 pixels away
+        let (p1, p2) = (PointF64::new(3.0, 4.0), PointF64::new(4.0, 4.0));
+        let (p3, p4) = (PointF64::new(3.0, 5.0), PointF64::new(2.0, 5.0001));
+
+        // WHEN
+        let result = intersect(p1, p2, p3, p4);
+
+        // THEN
+        assert!(matches!(result, LineIntersectionResult::Parallel));
+    }
+
+    #[test]
+    fn intersection_nearly_coincident_lines_are_coincident() {
+        // GIVEN
+        let (p1, p2) = (PointF64::new(0.0, 0.0), PointF64::new(1.0, 1.0));
+        let (p3, p4) = (PointF64::new(20.0, 20.00001), PointF64::new(19.0, 19.0));
+
+        // WHEN
+        let result = intersect(p1, p2, p3, p4);
+
+        // THEN
+        assert!(matches!(result, LineIntersectionResult::Coincidence));
+    }
+
+    #[test]
+    fn intersection_does_not_depend_on_scale() {
+        // GIVEN
+        let points = [
+            PointF64::new(1.0, 2.0),
+            PointF64::new(3.0, 3.0),
+            PointF64::new(9.0, 1.0),
+            PointF64::new(8.0, 3.0),
+        ];
+
+        for &scale in &[1e-6, 1.0, 1e6] {
+            // WHEN
+            let [p1, p2, p3, p4] = points.map(|point| point * scale);
+            let result = intersect(p1, p2, p3, p4);
+
+            // THEN
+            match result {
+                LineIntersectionResult::Intersect(intersection) => {
+                    assert!(
+                        intersection.distance_to(PointF64::new(7.0, 5.0) * scale) <= 1e-9 * scale
+                    )
+                }
+                _ => panic!(
+                    "Expected an intersection at scale {}, got {:?}",
+                    scale, result
+                ),
+            }
+        }
+    }
+
+    #[test]
+    fn intersection_behind_either_line_is_none() {
+        // GIVEN
+        let (p1, p2) = (PointF64::new(0.0, 0.0), PointF64::new(1.0, 0.0));
+        let (p3, p4) = (PointF64::new(-5.0, 5.0), PointF64::new(-5.0, 6.0));
+
+        // WHEN
+        let result = intersect(p1, p2, p3, p4);
+
+        // THEN
+        assert!(matches!(result, LineIntersectionResult::None));
+    }
+
+    #[test]
+    fn curves_closer_than_min_clearance_only_away_from_endpoints() {
+        // GIVEN
+        // Two curves starting 1 pixel apart, then diverging, then converging to 2 pixels apart
+        let curves = [
+            polyline(&[(0.0, 0.0), (10.0, 0.0), (20.0, 0.0)]),
+            polyline(&[(0.0, 1.0), (5.0, 4.0), (15.0, 2.0), (20.0, 6.0)]),
+        ];
+
+        // THEN
+        let curves = [&curves[0], &curves[1]];
+        assert!(!curves_closer_than(&curves, 1.5, 0.25));
+        assert!(curves_closer_than(&curves, 2.5, 0.25));
+    }
+
+    #[test]
+    fn intersection_of_multi_curve_spline_and_polyline() {
+        // GIVEN
+        // A spline of 2 curves rising from (0, 0) to (10, 10) and falling back to (20, 0),
+        // and a polyline crossing only its second curve
+        let curve = spline(&[
+            (0.0, 0.0),
+            (3.0, 5.0),
+            (7.0, 10.0),
+            (10.0, 10.0),
+            (13.0, 10.0),
+            (17.0, 5.0),
+            (20.0, 0.0),
+        ]);
+        let crossing_polyline = polyline(&[(15.0, -5.0), (15.0, 2.0), (25.0, 2.0)]);
+        let remote_polyline = polyline(&[(115.0, -5.0), (115.0, 2.0), (125.0, 2.0)]);
+
+        // WHEN
+        let crossing = bezier_curves_intersection(&[&curve, &crossing_polyline], 0.25);
+        let remote = bezier_curves_intersection(&[&curve, &remote_polyline], 0.25);
+
+        // THEN
+        assert!(crossing);
+        assert!(!remote);
+    }
+
+    #[test]
+    fn intersection_of_touching_curves_at_fine_tolerance() {
+        // GIVEN
+        // A line along the x-axis, and two curves sagging towards it from above,
+        // one dipping 0.02 pixels below it and the other staying 0.1 pixels above it
+        let line = polyline(&[(0.0, 0.0), (20.0, 0.0)]);
+        let sagging_curve = |lowest_y: f64| {
+            let control_y = (1.25 - lowest_y) / 0.75;
+            spline(&[
+                (0.0, 5.0),
+                (5.0, -control_y),
+                (15.0, -control_y),
+                (20.0, 5.0),
+            ])
+        };
+        let (dipping_curve, hovering_curve) = (sagging_curve(-0.02), sagging_curve(0.1));
+
+        // WHEN
+        let dipping = bezier_curves_intersection(&[&line, &dipping_curve], 0.05);
+        let hovering = bezier_curves_intersection(&[&line, &hovering_curve], 0.05);
+
+        // THEN
+        assert!(dipping);
+        assert!(!hovering);
+    }
+
+    #[test]
+    fn corners_at_scale_detect_rounded_corner_once() {
+        // GIVEN
+        // A right angle rounded over 4 points, each turning by 22.5 degrees
+        let mut points = vec![PointF64::new(-10.0, 0.0), PointF64::new(0.0, 0.0)];
+        (1..=4).for_each(|i| {
+            let angle = std::f64::consts::FRAC_PI_8 * i as f64;
+            let direction = PointF64::new(angle.cos(), angle.sin());
+            points.push(*points.last().unwrap() + direction);
+        });
+        points.push(*points.last().unwrap() + PointF64::new(0.0, 10.0));
+        let path = PathF64::from_points(points);
+        let threshold = std::f64::consts::FRAC_PI_4;
+
+        // WHEN
+        let single_scale_corners = find_corners_open_path_at_scale(&path, threshold, 0.0);
+        let multi_scale_corners = find_corners_open_path_at_scale(&path, threshold, 4.0);
+
+        // THEN
+        assert!(single_scale_corners.iter().all(|&is_corner| !is_corner));
+        assert_eq!(
+            multi_scale_corners
+                .iter()
+                .filter(|&&is_corner| is_corner)
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn corners_at_scale_ignore_noise() {
+        // GIVEN
+        // A straight line with a zigzag of 0.5 pixels
+        let path = PathF64::from_points(
+            (0..20)
+                .map(|i| PointF64::new(i as f64, if i % 2 == 0 { 0.0 } else { 0.5 }))
+                .collect(),
+        );
+        let threshold = std::f64::consts::FRAC_PI_4;
+
+        // WHEN
+        let single_scale_corners = find_corners_open_path_at_scale(&path, threshold, 0.0);
+        let multi_scale_corners = find_corners_open_path_at_scale(&path, threshold, 3.0);
+
+        // THEN
+        assert!(single_scale_corners.iter().any(|&is_corner| is_corner));
+        assert!(multi_scale_corners.iter().all(|&is_corner| !is_corner));
+    }
+
+    #[test]
+    fn directions_parallel_within_angular_tolerance() {
+        // GIVEN
+        let a = PointF64::new(1.0, 0.0);
+        let b = rotate_vector(PointF64::new(-2.0, 0.0), 0.05);
+
+        // THEN
+        assert!(are_directions_parallel(a, b, 0.06));
+        assert!(!are_directions_parallel(a, b, 0.04));
+        assert!(are_directions_parallel(a, a * 1e9, 0.0));
+    }
+
+    #[test]
+    fn bending_energy_is_integral_of_squared_curvature_over_arc_length() {
+        // GIVEN
+        // Bezier approximations of quarter circles, whose energy is (1 / r^2) * (pi * r / 2)
+        let quarter_circle = |radius: f64| {
+            let k = 0.5523 * radius;
+            [
+                PointF64::new(radius, 0.0),
+                PointF64::new(radius, k),
+                PointF64::new(k, radius),
+                PointF64::new(0.0, radius),
+            ]
+        };
+        let straight_line = [
+            PointF64::new(0.0, 0.0),
+            PointF64::new(1.0, 0.0),
+            PointF64::new(9.0, 0.0),
+            PointF64::new(10.0, 0.0),
+        ];
+
+        // THEN
+        for &radius in [10.0, 40.0].iter() {
+            let expected = std::f64::consts::FRAC_PI_2 / radius;
+            let energy = calculate_bending_energy(&quarter_circle(radius));
+            assert!((energy - expected).abs() < expected * 0.01);
+        }
+        assert!(calculate_bending_energy(&straight_line).abs() < f64::EPSILON);
+    }
+}
@@ -1,7 +1,17 @@
+pub mod biarc;
+pub mod clothoid;
 pub mod completor;
 pub mod curve;
+pub mod curve_model;
 pub mod debugger;
+pub mod diffusion;
 pub mod filler;
 mod geo;
 pub mod matcher;
 pub mod matcher_helper;
+pub mod quintic;
+pub mod refinement;
+mod region;
+pub mod symmetry;
+pub mod tail;
+pub mod texture;
@@ -12,32 +12,84 @@ use crate::matcher_helper::{MatchItem, MatchItemSet, Matching, SquareDistanceMat
 /// find a complete, disjoint, pair matching of those points such that the sum of distances between the pairs is at minimum.
 pub struct Matcher;
 
-// API
-impl Matcher {
-    /// Find the overall 'optimal' matching. Definition of optimality is to be determined.
-    /// The behavior is undefined unless 'match_items' contains n items where n is even and n>0.
-    /// 'direction_difference_threshold': [0.0, 1.0]
-    #[deprecated(note = "`find_all_possible_matchings` is currently in use.")]
-    pub fn find_matching(
-        match_items: MatchItemSet,
-        direction_difference_threshold: f64,
-    ) -> Matching {
+/// An algorithm producing candidate matchings of endpoints.
+/// The returned matchings are ordered from the most preferred to the least preferred;
+/// the caller tries them in order until one of them can be intrapolated.
+pub trait MatchingStrategy {
+    /// Return an Err if 'match_items' is empty or contains an odd number of items.
+    fn find_matchings(&self, match_items: MatchItemSet) -> Result<Vec<Matching>, String>;
+}
+
+/// Try every balanced partition of the match items and keep the unique matchings,
+/// sorted by the direction variance of their partitions.
+/// The number of partitions grows combinatorially with the number of match items.
+#[derive(Default)]
+pub struct ExhaustivePartitionStrategy;
+
+/// Partition the match items once by direction (or by distance as a fallback) and
+/// return the single matching of that partition, where pairs are weighted by the confidences of their directions
+/// as in 'ExhaustivePartitionStrategy'. The partition itself does not depend on the confidences.
+/// Runs in polynomial time, suitable for large numbers of match items.
+pub struct DirectionPartitionStrategy {
+    /// [0.0, 1.0]
+    pub direction_difference_threshold: f64,
+}
+
+impl Default for DirectionPartitionStrategy {
+    fn default() -> Self {
+        Self {
+            direction_difference_threshold: 0.5,
+        }
+    }
+}
+
+impl MatchingStrategy for ExhaustivePartitionStrategy {
+    fn find_matchings(&self, match_items: MatchItemSet) -> Result<Vec<Matching>, String> {
+        Matcher::find_all_possible_matchings(match_items)
+    }
+}
+
+impl MatchingStrategy for DirectionPartitionStrategy {
+    fn find_matchings(&self, match_items: MatchItemSet) -> Result<Vec<Matching>, String> {
         let len = match_items.len();
-        assert_eq!(len % 2, 0);
-        assert!(len > 0);
+        if len % 2 != 0 {
+            return Err("There must be an even number of match items.".into());
+        }
+        if len == 0 {
+            return Err("There must be some match items.".into());
+        }
 
-        let (set1, set2) = Self::partition(match_items, direction_difference_threshold);
+        let (set1, set2) = Matcher::partition(match_items, self.direction_difference_threshold);
 
-        let distance_matrix = SquareDistanceMatrix::from_two_sets(&set1, &set2);
+        let distance_matrix = SquareDistanceMatrix::from_two_sets_weighted(&set1, &set2);
 
         let index_matching = distance_matrix.into_matching();
 
-        Matching::from_pairs(
+        Ok(vec![Matching::from_pairs(
             index_matching
                 .into_iter()
                 .map(|(index1, index2)| (set1[index1].id, set2[index2].id))
                 .collect(),
-        )
+        )])
+    }
+}
+
+// API
+impl Matcher {
+    /// Find the overall 'optimal' matching. Definition of optimality is to be determined.
+    /// The behavior is undefined unless 'match_items' contains n items where n is even and n>0.
+    /// 'direction_difference_threshold': [0.0, 1.0]
+    #[deprecated(note = "`find_all_possible_matchings` is currently in use.")]
+    pub fn find_matching(
+        match_items: MatchItemSet,
+        direction_difference_threshold: f64,
+    ) -> Matching {
+        DirectionPartitionStrategy {
+            direction_difference_threshold,
+        }
+        .find_matchings(match_items)
+        .unwrap()
+        .remove(0)
     }
 
     /// Find all possible matchings for each possible partition.
@@ -76,7 +128,7 @@ impl Matcher {
 
                 let variance = Self::calculate_average_variance(&set1, &set2);
 
-                let distance_matrix = SquareDistanceMatrix::from_two_sets(&set1, &set2);
+                let distance_matrix = SquareDistanceMatrix::from_two_sets_weighted(&set1, &set2);
 
                 let index_matching = distance_matrix.into_matching();
 
@@ -296,3 +348,110 @@ impl Matcher {
         (set1, set2)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two horizontal strokes crossing a hole: 0 and 1 on the left pointing right, 2 and 3 on the right pointing left
+    fn two_strokes_across_hole() -> MatchItemSet {
+        MatchItemSet::from_match_items_and_set_ids(vec![
+            MatchItem::new_with_default_id(PointF64::new(0.0, 0.0), PointF64::new(1.0, 0.0)),
+            MatchItem::new_with_default_id(PointF64::new(0.0, 10.0), PointF64::new(1.0, 0.0)),
+            MatchItem::new_with_default_id(PointF64::new(20.0, 0.0), PointF64::new(-1.0, 0.0)),
+            MatchItem::new_with_default_id(PointF64::new(20.0, 10.0), PointF64::new(-1.0, 0.0)),
+        ])
+    }
+
+    #[test]
+    fn strategies_prefer_matching_strokes_across_hole() {
+        // GIVEN
+        let strategies: Vec<Box<dyn MatchingStrategy>> = vec![
+            Box::new(ExhaustivePartitionStrategy),
+            Box::new(DirectionPartitionStrategy::default()),
+        ];
+
+        for strategy in strategies {
+            // WHEN
+            let matchings = strategy.find_matchings(two_strokes_across_hole()).unwrap();
+
+            // THEN
+            assert_eq!(matchings[0], Matching::from_pairs(vec![(0, 2), (1, 3)]));
+        }
+    }
+
+    #[test]
+    fn exhaustive_strategy_returns_each_matching_once() {
+        // GIVEN
+        let match_items = two_strokes_across_hole();
+
+        // WHEN
+        let matchings = ExhaustivePartitionStrategy
+            .find_matchings(match_items)
+            .unwrap();
+
+        // THEN
+        // Only 3 partitions of 4 items are tried, and two of them share their best matching,
+        // which is returned once
+        assert_eq!(matchings.len(), 2);
+        assert_ne!(matchings[0], matchings[1]);
+        assert_eq!(matchings[0], Matching::from_pairs(vec![(0, 2), (1, 3)]));
+        assert_eq!(matchings[1], Matching::from_pairs(vec![(0, 1), (2, 3)]));
+    }
+
+    #[test]
+    fn low_confidence_tail_changes_chosen_matching() {
+        // GIVEN
+        // Item 2 points away from item 0, which is closer to it than item 1
+        let match_items = |confidence| {
+            MatchItemSet::from_match_items_and_set_ids(vec![
+                MatchItem::new_with_default_id(PointF64::new(0.0, 0.0), PointF64::new(1.0, 0.0)),
+                MatchItem::new_with_default_id(PointF64::new(0.0, 10.0), PointF64::new(1.0, 0.0)),
+                MatchItem::new_with_default_id(PointF64::new(-10.0, 0.0), PointF64::new(0.0, -1.0))
+                    .with_confidence(confidence),
+                MatchItem::new_with_default_id(PointF64::new(20.0, 0.0), PointF64::new(0.0, -1.0)),
+            ])
+        };
+        let strategies: Vec<Box<dyn MatchingStrategy>> = vec![
+            Box::new(ExhaustivePartitionStrategy),
+            Box::new(DirectionPartitionStrategy::default()),
+        ];
+
+        for strategy in strategies {
+            // WHEN
+            let trusted_matchings = strategy.find_matchings(match_items(1.0)).unwrap();
+            let untrusted_matchings = strategy.find_matchings(match_items(0.0)).unwrap();
+
+            // THEN
+            // The misleading direction is only ignored when it is not trusted
+            assert_eq!(
+                trusted_matchings[0],
+                Matching::from_pairs(vec![(0, 2), (1, 3)])
+            );
+            assert_eq!(
+                untrusted_matchings[0],
+                Matching::from_pairs(vec![(1, 2), (0, 3)])
+            );
+        }
+    }
+
+    #[test]
+    fn strategies_reject_odd_number_of_items() {
+        // GIVEN
+        let odd_items = || {
+            MatchItemSet::from_match_items_and_set_ids(vec![
+                MatchItem::new_with_default_id(PointF64::new(0.0, 0.0), PointF64::new(1.0, 0.0)),
+                MatchItem::new_with_default_id(PointF64::new(0.0, 10.0), PointF64::new(1.0, 0.0)),
+                MatchItem::new_with_default_id(PointF64::new(20.0, 0.0), PointF64::new(-1.0, 0.0)),
+            ])
+        };
+
+        // WHEN
+        let exhaustive_result = ExhaustivePartitionStrategy.find_matchings(odd_items());
+        let direction_result = DirectionPartitionStrategy::default().find_matchings(odd_items());
+
+        // THEN
+        assert!(exhaustive_result.is_err());
+        assert!(direction_result.is_err());
+    }
+}
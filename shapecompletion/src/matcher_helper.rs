@@ -13,6 +13,8 @@ pub struct MatchItem {
     pub id: usize,
     pub point: PointF64,
     pub direction: PointF64,
+    /// How reliable 'direction' is, in [0.0, 1.0]
+    pub confidence: f64,
 }
 
 #[derive(Debug, Default)]
@@ -43,13 +45,36 @@ impl Distanced for MatchItem {
 
 impl MatchItem {
     /// Create a MatchItem with a default id and the specified 'point' and 'direction'.
+    /// 'direction' is fully trusted.
     pub fn new_with_default_id(point: PointF64, direction: PointF64) -> Self {
         Self {
             id: Default::default(),
             point,
             direction,
+            confidence: 1.0,
         }
     }
+
+    pub fn with_confidence(mut self, confidence: f64) -> Self {
+        self.confidence = confidence;
+        self
+    }
+
+    /// The cost of pairing up with 'other'.
+    /// It is the distance between the items, scaled up by at most 2 times if their directions do not
+    /// point towards each other. The penalty is weighted by the confidences of both directions.
+    pub fn weighted_cost_to(&self, other: &Self) -> f64 {
+        let distance = self.distance_to(other);
+        if distance < f64::EPSILON {
+            return distance;
+        }
+
+        let to_other = (other.point - self.point) / distance;
+        // 1.0 when both directions point towards each other, -1.0 when both point away
+        let agreement = (self.direction.dot(to_other) - other.direction.dot(to_other)) / 2.0;
+        let penalty = (1.0 - agreement) / 2.0;
+        distance * (1.0 + self.confidence * other.confidence * penalty)
+    }
 }
 
 impl Index<usize> for MatchItemSet {
@@ -228,6 +253,23 @@ impl SquareDistanceMatrix {
         Self { n, distances }
     }
 
+    /// Same as 'from_two_sets', but using the confidence-weighted costs of the pairs instead of distances.
+    /// The behavior is undefined unless 'set1' and 'set2' have the same number of items.
+    pub fn from_two_sets_weighted(set1: &MatchItemSet, set2: &MatchItemSet) -> Self {
+        assert_eq!(set1.len(), set2.len());
+        let n = set1.len();
+
+        let mut distances = vec![0.0; n * n];
+
+        for i in 0..n {
+            for j in 0..n {
+                distances[i * n + j] = set1[i].weighted_cost_to(&set2[j]);
+            }
+        }
+
+        Self { n, distances }
+    }
+
     pub fn into_matching(self) -> Matching {
         let n = self.n;
         let matrix: Vec<u64> = self.distances.into_iter().map(|dist| dist as u64).collect();
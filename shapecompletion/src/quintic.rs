@@ -0,0 +1,165 @@
+use visioniechor::{PathF64, PointF64};
+
+use crate::geo::rotate_vector;
+
+/// A quintic curve interpolating positions, first and second derivatives at both ends,
+/// stored as its 6 bezier control points.
+#[derive(Clone, Copy, Debug)]
+pub struct QuinticHermite {
+    pub control_points: [PointF64; 6],
+}
+
+// API
+impl QuinticHermite {
+    pub fn new(
+        from_point: PointF64,
+        from_derivative: PointF64,
+        from_second_derivative: PointF64,
+        to_point: PointF64,
+        to_derivative: PointF64,
+        to_second_derivative: PointF64,
+    ) -> Self {
+        Self {
+            control_points: [
+                from_point,
+                from_point + from_derivative / 5.0,
+                from_point + from_derivative * 0.4 + from_second_derivative / 20.0,
+                to_point - to_derivative * 0.4 + to_second_derivative / 20.0,
+                to_point - to_derivative / 5.0,
+                to_point,
+            ],
+        }
+    }
+
+    /// Construct the curve leaving 'from_point' in 'from_direction' with signed curvature 'from_curvature',
+    /// and arriving at 'to_point' in 'to_direction' with signed curvature 'to_curvature'.
+    /// The speed is the base length throughout the ends, so that the curve is as close to uniform as possible.
+    pub fn from_directions_and_curvatures(
+        from_point: PointF64,
+        from_direction: PointF64,
+        from_curvature: f64,
+        to_point: PointF64,
+        to_direction: PointF64,
+        to_curvature: f64,
+    ) -> Self {
+        let speed = from_point.distance_to(to_point);
+        let (from_direction, to_direction) = (
+            from_direction.get_normalized(),
+            to_direction.get_normalized(),
+        );
+        let normal = |direction: PointF64| rotate_vector(direction, std::f64::consts::FRAC_PI_2);
+
+        Self::new(
+            from_point,
+            from_direction * speed,
+            normal(from_direction) * (from_curvature * speed * speed),
+            to_point,
+            to_direction * speed,
+            normal(to_direction) * (to_curvature * speed * speed),
+        )
+    }
+
+    /// Evaluate the curve at 't' in [0.0, 1.0] by de Casteljau's algorithm.
+    pub fn point_at(&self, t: f64) -> PointF64 {
+        let mut points = self.control_points;
+        for len in (1..points.len()).rev() {
+            for i in 0..len {
+                points[i] = points[i] + (points[i + 1] - points[i]) * t;
+            }
+        }
+        points[0]
+    }
+
+    /// Sample the curve into a polyline, whose consecutive points are at most about 'max_step' apart.
+    pub fn to_path(&self, max_step: f64) -> PathF64 {
+        // The control polygon is never shorter than the curve
+        let polygon_length: f64 = self
+            .control_points
+            .windows(2)
+            .map(|points| points[0].distance_to(points[1]))
+            .sum();
+        let num_steps = std::cmp::max((polygon_length / max_step).ceil() as usize, 1);
+        PathF64::from_points(
+            (0..=num_steps)
+                .map(|i| self.point_at(i as f64 / num_steps as f64))
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geo::cross_product;
+
+    fn points_approximately(a: PointF64, b: PointF64) -> bool {
+        a.distance_to(b) <= 1e-6
+    }
+
+    #[test]
+    fn quintic_interpolates_endpoints_directions_and_curvatures() {
+        // GIVEN
+        let (from_point, to_point) = (PointF64::new(3.0, 4.0), PointF64::new(25.0, 11.0));
+        let from_direction = PointF64::new(1.0, -1.0).get_normalized();
+        let to_direction = PointF64::new(0.6, 0.8);
+        let (from_curvature, to_curvature) = (0.05, -0.1);
+
+        // WHEN
+        let curve = QuinticHermite::from_directions_and_curvatures(
+            from_point,
+            from_direction,
+            from_curvature,
+            to_point,
+            to_direction,
+            to_curvature,
+        );
+
+        // THEN
+        let p = curve.control_points;
+        // Derivatives of the bezier curve at both ends
+        let (from_derivative, to_derivative) = ((p[1] - p[0]) * 5.0, (p[5] - p[4]) * 5.0);
+        let from_second_derivative = (p[2] - p[1] * 2.0 + p[0]) * 20.0;
+        let to_second_derivative = (p[5] - p[4] * 2.0 + p[3]) * 20.0;
+        let curvature = |derivative: PointF64, second_derivative: PointF64| {
+            cross_product(derivative, second_derivative) / derivative.norm().powi(3)
+        };
+
+        assert!(points_approximately(curve.point_at(0.0), from_point));
+        assert!(points_approximately(curve.point_at(1.0), to_point));
+        assert!(points_approximately(
+            from_derivative.get_normalized(),
+            from_direction
+        ));
+        assert!(points_approximately(
+            to_derivative.get_normalized(),
+            to_direction
+        ));
+        assert!(
+            (curvature(from_derivative, from_second_derivative) - from_curvature).abs() <= 1e-9
+        );
+        assert!((curvature(to_derivative, to_second_derivative) - to_curvature).abs() <= 1e-9);
+    }
+
+    #[test]
+    fn quintic_path_starts_and_ends_at_endpoints() {
+        // GIVEN
+        let curve = QuinticHermite::from_directions_and_curvatures(
+            PointF64::new(0.0, 0.0),
+            PointF64::new(1.0, 0.0),
+            0.0,
+            PointF64::new(10.0, 10.0),
+            PointF64::new(0.0, 1.0),
+            0.0,
+        );
+
+        // WHEN
+        let path = curve.to_path(0.5);
+
+        // THEN
+        assert!(points_approximately(path[0], PointF64::new(0.0, 0.0)));
+        assert!(points_approximately(
+            path[path.len() - 1],
+            PointF64::new(10.0, 10.0)
+        ));
+    }
+}
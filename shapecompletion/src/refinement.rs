@@ -0,0 +1,233 @@
+use visioniechor::{BoundingRect, CompoundPath, PointF64, Spline};
+
+use crate::geo::{
+    calculate_bending_energy, sample_compound_path, sampled_curves_closer_than,
+    CLEARANCE_SAMPLING_RATIO,
+};
+
+/// The sampling step in pixels of the curves checked against the hole
+const HOLE_SAMPLING_STEP: f64 = 0.5;
+
+#[derive(Clone, Copy, Debug)]
+pub struct RefinementConfig {
+    /// Number of lengths tried for each control point in the initial search
+    pub num_length_steps: usize,
+    /// Minimum distance of a control point from its endpoint, as a ratio of the distance between the endpoints
+    pub min_length_ratio: f64,
+    /// Maximum distance of a control point from its endpoint, as a ratio of the distance between the endpoints
+    pub max_length_ratio: f64,
+    /// Number of times the best lengths of the initial search are improved locally, halving the step each time
+    pub num_local_iterations: usize,
+}
+
+impl Default for RefinementConfig {
+    fn default() -> Self {
+        Self {
+            num_length_steps: 16,
+            min_length_ratio: 0.05,
+            max_length_ratio: 1.0,
+            num_local_iterations: 6,
+        }
+    }
+}
+
+/// Refine cubic bezier curves by choosing the distances of their control points from their endpoints
+/// (along the given tangents) such that the bending energy is the minimum, subject to the curve staying inside
+/// the hole and away from other curves.
+pub struct CurveRefiner {
+    pub config: RefinementConfig,
+    pub hole_rect: BoundingRect,
+    /// Maximum distance by which a refined curve may leave the hole
+    pub hole_tolerance: f64,
+}
+
+// API
+impl CurveRefiner {
+    pub fn new(config: RefinementConfig, hole_rect: BoundingRect, hole_tolerance: f64) -> Self {
+        Self {
+            config,
+            hole_rect,
+            hole_tolerance,
+        }
+    }
+
+    /// Return the control points of the refined curve, which has the same endpoints as the curve with
+    /// 'control_points', and leaves and enters them in the same directions.
+    /// The refined curve stays within 'hole_tolerance' of the hole, and 'clearance' away from every curve
+    /// in 'obstacles' (see 'geo::curves_closer_than').
+    /// Return None if no lengths satisfy the constraints, or a control point coincides with its endpoint.
+    pub fn refine(
+        &self,
+        control_points: [PointF64; 4],
+        obstacles: &[&CompoundPath],
+        clearance: f64,
+    ) -> Option<[PointF64; 4]> {
+        let [from_point, control_point1, control_point2, to_point] = control_points;
+        let base_length = from_point.distance_to(to_point);
+        let (length1, length2) = (
+            from_point.distance_to(control_point1),
+            to_point.distance_to(control_point2),
+        );
+        if base_length < f64::EPSILON || length1 < f64::EPSILON || length2 < f64::EPSILON {
+            return None;
+        }
+        let (direction1, direction2) = (
+            (control_point1 - from_point) / length1,
+            (control_point2 - to_point) / length2,
+        );
+        let curve_with_lengths = |(length1, length2): (f64, f64)| {
+            [
+                from_point,
+                from_point + direction1 * length1,
+                to_point + direction2 * length2,
+                to_point,
+            ]
+        };
+        // The obstacles do not change, so they are sampled once for all the evaluations
+        let sampling_step = if clearance > 0.0 {
+            f64::min(HOLE_SAMPLING_STEP, clearance * CLEARANCE_SAMPLING_RATIO)
+        } else {
+            HOLE_SAMPLING_STEP
+        };
+        let obstacle_samples: Vec<Vec<PointF64>> = obstacles
+            .iter()
+            .map(|obstacle| sample_compound_path(obstacle, sampling_step))
+            .collect();
+        let evaluate = |lengths: (f64, f64)| {
+            let points = curve_with_lengths(lengths);
+            if self.is_feasible(points, &obstacle_samples, clearance, sampling_step) {
+                Some(calculate_bending_energy(&points))
+            } else {
+                None
+            }
+        };
+
+        //# Initial search
+        let (min_length, max_length) = (
+            base_length * self.config.min_length_ratio,
+            base_length * self.config.max_length_ratio,
+        );
+        let num_steps = std::cmp::max(self.config.num_length_steps, 2);
+        let mut step = (max_length - min_length) / (num_steps - 1) as f64;
+        let lengths = (0..num_steps).map(|i| min_length + step * i as f64);
+        let (mut best_lengths, mut best_energy) = lengths
+            .clone()
+            .flat_map(|length1| lengths.clone().map(move |length2| (length1, length2)))
+            .filter_map(|lengths| evaluate(lengths).map(|energy| (lengths, energy)))
+            .min_by(|(_, energy1), (_, energy2)| energy1.total_cmp(energy2))?;
+
+        //# Local improvement
+        for _ in 0..self.config.num_local_iterations {
+            step *= 0.5;
+            let (length1, length2) = best_lengths;
+            let neighbors = [
+                (length1 - step, length2),
+                (length1 + step, length2),
+                (length1, length2 - step),
+                (length1, length2 + step),
+            ];
+            neighbors
+                .iter()
+                .filter(|&&(length1, length2)| {
+                    (min_length..=max_length).contains(&length1)
+                        && (min_length..=max_length).contains(&length2)
+                })
+                .for_each(|&lengths| {
+                    if let Some(energy) = evaluate(lengths) {
+                        if energy < best_energy {
+                            best_lengths = lengths;
+                            best_energy = energy;
+                        }
+                    }
+                });
+        }
+
+        Some(curve_with_lengths(best_lengths))
+    }
+
+    /// Refine every curve in 'spline' (see 'refine').
+    pub fn refine_spline(
+        &self,
+        spline: &Spline,
+        obstacles: &[&CompoundPath],
+        clearance: f64,
+    ) -> Option<Spline> {
+        let mut refined = Spline::new(*spline.points.first()?);
+        for points in spline.get_control_points() {
+            let [_, control_point1, control_point2, to_point] = self.refine(
+                [points[0], points[1], points[2], points[3]],
+                obstacles,
+                clearance,
+            )?;
+            refined.add(control_point1, control_point2, to_point);
+        }
+        Some(refined)
+    }
+}
+
+// Helper functions
+impl CurveRefiner {
+    /// Return true iff the curve with control points 'points' stays within 'hole_tolerance' of the hole,
+    /// and 'clearance' away from the curves sampled in 'obstacle_samples', where all curves are sampled 'step' apart.
+    fn is_feasible(
+        &self,
+        points: [PointF64; 4],
+        obstacle_samples: &[Vec<PointF64>],
+        clearance: f64,
+        step: f64,
+    ) -> bool {
+        let mut curve = CompoundPath::new();
+        let mut spline = Spline::new(points[0]);
+        spline.add(points[1], points[2], points[3]);
+        curve.add_spline(spline);
+
+        let is_inside_hole = |point: &PointF64| {
+            self.hole_rect.left as f64 - self.hole_tolerance <= point.x
+                && point.x <= self.hole_rect.right as f64 + self.hole_tolerance
+                && self.hole_rect.top as f64 - self.hole_tolerance <= point.y
+                && point.y <= self.hole_rect.bottom as f64 + self.hole_tolerance
+        };
+
+        let samples = sample_compound_path(&curve, step);
+        samples.iter().all(is_inside_hole)
+            && (clearance <= 0.0
+                || obstacle_samples.iter().all(|obstacle| {
+                    !sampled_curves_closer_than(&[&samples, obstacle], clearance, step)
+                }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refine_keeps_endpoints_and_directions_inside_hole() {
+        // GIVEN
+        // A U-turn whose control points are far outside the hole
+        let hole_rect = BoundingRect::new_x_y_w_h(0, 0, 20, 20);
+        let refiner = CurveRefiner::new(RefinementConfig::default(), hole_rect, 0.0);
+        let control_points = [
+            PointF64::new(0.0, 5.0),
+            PointF64::new(40.0, 5.0),
+            PointF64::new(40.0, 15.0),
+            PointF64::new(0.0, 15.0),
+        ];
+
+        // WHEN
+        let refined = refiner.refine(control_points, &[], 0.0).unwrap();
+
+        // THEN
+        assert_eq!(refined[0], control_points[0]);
+        assert_eq!(refined[3], control_points[3]);
+        assert!((refined[1] - refined[0]).get_normalized().x > 1.0 - 1e-9);
+        assert!((refined[2] - refined[3]).get_normalized().x > 1.0 - 1e-9);
+        let mut curve = CompoundPath::new();
+        let mut spline = Spline::new(refined[0]);
+        spline.add(refined[1], refined[2], refined[3]);
+        curve.add_spline(spline);
+        assert!(sample_compound_path(&curve, 0.5)
+            .iter()
+            .all(|point| point.x <= 20.0 && point.y >= 0.0 && point.y <= 20.0));
+    }
+}
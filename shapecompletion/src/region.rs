@@ -0,0 +1,86 @@
+use visioniechor::{BinaryImage, PointI32};
+
+// Pixel region helper functions
+
+/// The 4 neighbors of 'point', which may lie outside the image
+pub(super) fn four_neighbors(point: PointI32) -> impl Iterator<Item = PointI32> {
+    IntoIterator::into_iter([
+        PointI32::new(1, 0),
+        PointI32::new(0, 1),
+        PointI32::new(-1, 0),
+        PointI32::new(0, -1),
+    ])
+    .map(move |offset| point + offset)
+}
+
+/// Flood fill the 4-connected region containing 'seed' in a 'width' x 'height' grid, in an iterative manner.
+/// 'try_visit' is called on the points reached inside the grid, and returns true iff the point joins the region,
+/// in which case the fill continues to its neighbors. 'try_visit' must mark the points it accepts,
+/// so that it rejects them when they are reached again.
+pub(super) fn flood_fill_4_connected<F>(
+    seed: PointI32,
+    width: usize,
+    height: usize,
+    mut try_visit: F,
+) where
+    F: FnMut(PointI32) -> bool,
+{
+    let mut stack = vec![seed];
+    while let Some(point) = stack.pop() {
+        let is_inside_grid =
+            0 <= point.x && point.x < width as i32 && 0 <= point.y && point.y < height as i32;
+        if is_inside_grid && try_visit(point) {
+            stack.extend(four_neighbors(point));
+        }
+    }
+}
+
+/// Label the 4-connected regions of pixels of the same value in 'image', both foreground and background.
+/// Return the label of each pixel (row by row) with the number of regions.
+pub(super) fn label_4_connected_regions(image: &BinaryImage) -> (Vec<usize>, usize) {
+    let index_of = |point: PointI32| point.y as usize * image.width + point.x as usize;
+    let mut labels = vec![usize::MAX; image.width * image.height];
+    let mut num_labels = 0;
+    for y in 0..image.height as i32 {
+        for x in 0..image.width as i32 {
+            let seed = PointI32::new(x, y);
+            if labels[index_of(seed)] != usize::MAX {
+                continue;
+            }
+            let value = image.get_pixel_at(seed);
+            flood_fill_4_connected(seed, image.width, image.height, |point| {
+                let label = &mut labels[index_of(point)];
+                if *label != usize::MAX || image.get_pixel_at(point) != value {
+                    return false;
+                }
+                *label = num_labels;
+                true
+            });
+            num_labels += 1;
+        }
+    }
+    (labels, num_labels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn label_4_connected_regions_separates_diagonal_neighbors() {
+        // GIVEN
+        // A foreground pixel touching another one only diagonally, surrounded by background
+        let mut image = BinaryImage::new_w_h(4, 4);
+        image.set_pixel(1, 1, true);
+        image.set_pixel(2, 2, true);
+
+        // WHEN
+        let (labels, num_labels) = label_4_connected_regions(&image);
+
+        // THEN
+        assert_eq!(num_labels, 3);
+        assert_ne!(labels[4 + 1], labels[2 * 4 + 2]);
+        assert_eq!(labels[0], labels[3 * 4 + 3]);
+        assert!(labels.iter().all(|&label| label < num_labels));
+    }
+}
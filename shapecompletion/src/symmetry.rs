@@ -0,0 +1,452 @@
+use visioniechor::{BinaryImage, BoundingRect, PointF64, PointI32, PointUsize};
+
+use crate::filler::{FilledHoleElement, FilledHoleMatrix};
+
+#[derive(Clone, Copy, Debug)]
+pub struct SymmetryConfig {
+    /// Number of axis orientations, evenly spaced in [0, pi), to be tested
+    pub num_axis_angles: usize,
+    /// Axes are searched within this distance (in pixels) from the centroid of the visible shape
+    /// in the search window, widened by half the extent of the hole along the axis normal,
+    /// since the shape hidden by the hole shifts the visible centroid away from the axis
+    pub max_axis_offset: f64,
+    pub axis_offset_step: f64,
+    /// Minimum fraction of contour pixels (with visible reflections) whose reflections are also contour pixels,
+    /// where a reflection next to a contour pixel counts as half
+    pub min_axis_score: f64,
+    /// Minimum fraction of contour pixels whose reflections are visible, for an axis to be considered at all
+    pub min_axis_support: f64,
+    /// Minimum fraction of pixels around the hole that agree with their reflections
+    pub min_boundary_agreement: f64,
+    /// The axis is searched for in the window extending this many times the larger side of the hole
+    /// beyond each side of the hole, so that other objects in the image do not affect it
+    pub search_window_ratio: f64,
+    /// Maximum number of contour pixels each candidate axis is scored against.
+    /// More contour pixels in the search window are subsampled evenly.
+    pub max_contour_points: usize,
+}
+
+impl Default for SymmetryConfig {
+    fn default() -> Self {
+        Self {
+            num_axis_angles: 36,
+            max_axis_offset: 16.0,
+            axis_offset_step: 0.5,
+            min_axis_score: 0.7,
+            min_axis_support: 0.5,
+            min_boundary_agreement: 0.9,
+            search_window_ratio: 2.0,
+            max_contour_points: 1000,
+        }
+    }
+}
+
+impl SymmetryConfig {
+    /// Return the config with all parameters in pixels multiplied by 'factor'.
+    pub fn scaled(&self, factor: f64) -> Self {
+        Self {
+            max_axis_offset: self.max_axis_offset * factor,
+            axis_offset_step: self.axis_offset_step * factor,
+            ..*self
+        }
+    }
+}
+
+/// A mirror line, consisting of the points p such that 'normal.dot(p) == offset'.
+#[derive(Clone, Copy, Debug)]
+pub struct ReflectionAxis {
+    /// Unit vector
+    pub normal: PointF64,
+    pub offset: f64,
+    /// Fraction of contour pixels (with visible reflections) whose reflections are also contour pixels
+    pub score: f64,
+}
+
+impl ReflectionAxis {
+    pub fn reflect(&self, point: PointF64) -> PointF64 {
+        point - self.normal * (2.0 * (self.normal.dot(point) - self.offset))
+    }
+
+    /// Reflect the center of the pixel at 'point', and return the pixel containing the result.
+    pub fn reflect_pixel(&self, point: PointI32) -> PointI32 {
+        let center = PointF64::new(point.x as f64 + 0.5, point.y as f64 + 0.5);
+        let reflected = self.reflect(center);
+        PointI32::new(reflected.x.floor() as i32, reflected.y.floor() as i32)
+    }
+}
+
+/// Complete the hole by the reflection of the visible image, if the visible shape is mirror-symmetric.
+/// Pixels inside the hole are unknown and never used as evidence.
+pub struct SymmetryCompletor {
+    pub config: SymmetryConfig,
+}
+
+// API
+impl SymmetryCompletor {
+    pub fn new(config: SymmetryConfig) -> Self {
+        Self { config }
+    }
+
+    /// Return the filled hole and the reflection axis, if a dominant reflection axis is detected,
+    /// the reflection of every pixel in the hole is visible, and the pixels around the hole agree with their reflections.
+    /// Return None otherwise.
+    pub fn complete(
+        &self,
+        image: &BinaryImage,
+        hole_rect: BoundingRect,
+    ) -> Option<(FilledHoleMatrix, ReflectionAxis)> {
+        let contour = Self::find_contour_pixels(image, &hole_rect);
+        let axis = self.detect_axis(image, &hole_rect, &contour)?;
+        if !self.does_reflection_agree_around_hole(image, &hole_rect, &axis) {
+            return None;
+        }
+        Self::reflect_into_hole(image, &hole_rect, &contour, &axis)
+            .map(|filled_hole| (filled_hole, axis))
+    }
+
+    /// Return the reflection axis under which the visible contour is the most self-similar,
+    /// or None if no axis reaches 'min_axis_score'.
+    pub fn detect_axis(
+        &self,
+        image: &BinaryImage,
+        hole_rect: &BoundingRect,
+        contour: &BinaryImage,
+    ) -> Option<ReflectionAxis> {
+        let window = self.calculate_search_window(image, hole_rect);
+        let contour_points: Vec<PointI32> = Self::visible_points_in(image, hole_rect, window)
+            .filter(|&point| contour.get_pixel_at(point))
+            .collect();
+        if contour_points.is_empty() {
+            return None;
+        }
+        let stride = contour_points
+            .len()
+            .div_ceil(std::cmp::max(self.config.max_contour_points, 1));
+        let contour_points: Vec<PointI32> = contour_points.into_iter().step_by(stride).collect();
+        let centroid = Self::calculate_foreground_centroid(image, hole_rect, window)?;
+
+        let mut best_axis: Option<ReflectionAxis> = None;
+        for i in 0..self.config.num_axis_angles {
+            let angle = std::f64::consts::PI * i as f64 / self.config.num_axis_angles as f64;
+            let normal = PointF64::new(angle.cos(), angle.sin());
+            let hole_half_extent = (hole_rect.width() as f64 * normal.x.abs()
+                + hole_rect.height() as f64 * normal.y.abs())
+                / 2.0;
+            let num_offsets = ((self.config.max_axis_offset + hole_half_extent)
+                / self.config.axis_offset_step)
+                .floor() as i32;
+            // Axes between two rows (or columns) of pixels have half-integer offsets
+            let base_offset = (normal.dot(centroid) * 2.0).round() / 2.0;
+            for k in -num_offsets..=num_offsets {
+                let mut axis = ReflectionAxis {
+                    normal,
+                    offset: base_offset + k as f64 * self.config.axis_offset_step,
+                    score: 0.0,
+                };
+                let (num_supported, num_matched) = Self::count_reflected_contour_matches(
+                    image,
+                    hole_rect,
+                    contour,
+                    &contour_points,
+                    &axis,
+                );
+                if (num_supported as f64)
+                    < self.config.min_axis_support * contour_points.len() as f64
+                {
+                    continue;
+                }
+                axis.score = num_matched / num_supported as f64;
+                if !matches!(best_axis, Some(best_axis) if best_axis.score >= axis.score) {
+                    best_axis = Some(axis);
+                }
+            }
+        }
+
+        best_axis.filter(|axis| axis.score >= self.config.min_axis_score)
+    }
+}
+
+// Helper functions
+impl SymmetryCompletor {
+    fn is_in_hole(hole_rect: &BoundingRect, point: PointI32) -> bool {
+        hole_rect.left <= point.x
+            && point.x < hole_rect.right
+            && hole_rect.top <= point.y
+            && point.y < hole_rect.bottom
+    }
+
+    /// A pixel is visible if it is inside the image but outside the hole.
+    fn is_visible(image: &BinaryImage, hole_rect: &BoundingRect, point: PointI32) -> bool {
+        0 <= point.x
+            && point.x < image.width as i32
+            && 0 <= point.y
+            && point.y < image.height as i32
+            && !Self::is_in_hole(hole_rect, point)
+    }
+
+    fn visible_points<'a>(
+        image: &'a BinaryImage,
+        hole_rect: &'a BoundingRect,
+    ) -> impl Iterator<Item = PointI32> + 'a {
+        let whole_image = BoundingRect::new_x_y_w_h(0, 0, image.width as i32, image.height as i32);
+        Self::visible_points_in(image, hole_rect, whole_image)
+    }
+
+    /// Same as 'visible_points', but only those in 'window'.
+    fn visible_points_in<'a>(
+        image: &'a BinaryImage,
+        hole_rect: &'a BoundingRect,
+        window: BoundingRect,
+    ) -> impl Iterator<Item = PointI32> + 'a {
+        (window.top..window.bottom)
+            .flat_map(move |y| (window.left..window.right).map(move |x| PointI32::new(x, y)))
+            .filter(move |&point| Self::is_visible(image, hole_rect, point))
+    }
+
+    /// The hole expanded by 'search_window_ratio' times its larger side on each side, clipped to the image.
+    fn calculate_search_window(
+        &self,
+        image: &BinaryImage,
+        hole_rect: &BoundingRect,
+    ) -> BoundingRect {
+        let margin = (std::cmp::max(hole_rect.width(), hole_rect.height()) as f64
+            * self.config.search_window_ratio)
+            .ceil() as i32;
+        let (left, top) = (
+            std::cmp::max(hole_rect.left - margin, 0),
+            std::cmp::max(hole_rect.top - margin, 0),
+        );
+        let (right, bottom) = (
+            std::cmp::min(hole_rect.right + margin, image.width as i32),
+            std::cmp::min(hole_rect.bottom + margin, image.height as i32),
+        );
+        BoundingRect::new_x_y_w_h(left, top, right - left, bottom - top)
+    }
+
+    /// Return the mask of visible foreground pixels with a background 4-neighbor.
+    /// Neighbors outside the image are background, neighbors inside the hole are unknown.
+    fn find_contour_pixels(image: &BinaryImage, hole_rect: &BoundingRect) -> BinaryImage {
+        let mut contour = BinaryImage::new_w_h(image.width, image.height);
+        let neighbor_offsets = [
+            PointI32::new(1, 0),
+            PointI32::new(-1, 0),
+            PointI32::new(0, 1),
+            PointI32::new(0, -1),
+        ];
+        Self::visible_points(image, hole_rect)
+            .filter(|&point| image.get_pixel_at(point))
+            .filter(|&point| {
+                neighbor_offsets.iter().any(|&offset| {
+                    let neighbor = point + offset;
+                    if Self::is_visible(image, hole_rect, neighbor) {
+                        !image.get_pixel_at(neighbor)
+                    } else {
+                        !Self::is_in_hole(hole_rect, neighbor)
+                    }
+                })
+            })
+            .for_each(|point| contour.set_pixel(point.x as usize, point.y as usize, true));
+        contour
+    }
+
+    /// The centroid of the visible foreground pixels in 'window'
+    fn calculate_foreground_centroid(
+        image: &BinaryImage,
+        hole_rect: &BoundingRect,
+        window: BoundingRect,
+    ) -> Option<PointF64> {
+        let (sum, count) = Self::visible_points_in(image, hole_rect, window)
+            .filter(|&point| image.get_pixel_at(point))
+            .fold((PointF64::default(), 0), |(sum, count), point| {
+                (
+                    sum + PointF64::new(point.x as f64 + 0.5, point.y as f64 + 0.5),
+                    count + 1,
+                )
+            });
+        if count == 0 {
+            None
+        } else {
+            Some(sum / count as f64)
+        }
+    }
+
+    /// Return the number of contour points whose reflections are visible, and the number of matches among them.
+    /// A reflection on a contour pixel is a full match, and one within 1 pixel from a contour pixel is a half match,
+    /// so that the exact axis scores higher than its neighbors.
+    fn count_reflected_contour_matches(
+        image: &BinaryImage,
+        hole_rect: &BoundingRect,
+        contour: &BinaryImage,
+        contour_points: &[PointI32],
+        axis: &ReflectionAxis,
+    ) -> (usize, f64) {
+        let mut num_supported = 0;
+        let mut num_matched = 0.0;
+        contour_points.iter().for_each(|&point| {
+            let reflected = axis.reflect_pixel(point);
+            if !Self::is_visible(image, hole_rect, reflected) {
+                return;
+            }
+            num_supported += 1;
+            if contour.get_pixel_at(reflected) {
+                num_matched += 1.0;
+            } else if (-1..=1).any(|dy| {
+                (-1..=1).any(|dx| contour.get_pixel_safe(reflected.x + dx, reflected.y + dy))
+            }) {
+                num_matched += 0.5;
+            }
+        });
+        (num_supported, num_matched)
+    }
+
+    /// Return true iff enough of the visible pixels just outside the hole agree with their visible reflections.
+    fn does_reflection_agree_around_hole(
+        &self,
+        image: &BinaryImage,
+        hole_rect: &BoundingRect,
+        axis: &ReflectionAxis,
+    ) -> bool {
+        let ring = BoundingRect::new_x_y_w_h(
+            hole_rect.left - 1,
+            hole_rect.top - 1,
+            hole_rect.width() + 2,
+            hole_rect.height() + 2,
+        );
+        let mut num_compared = 0;
+        let mut num_agreed = 0;
+        (ring.top..ring.bottom)
+            .flat_map(|y| (ring.left..ring.right).map(move |x| PointI32::new(x, y)))
+            .filter(|&point| Self::is_visible(image, hole_rect, point))
+            .for_each(|point| {
+                let reflected = axis.reflect_pixel(point);
+                if !Self::is_visible(image, hole_rect, reflected) {
+                    return;
+                }
+                num_compared += 1;
+                if image.get_pixel_at(point) == image.get_pixel_at(reflected) {
+                    num_agreed += 1;
+                }
+            });
+
+        num_compared > 0
+            && num_agreed as f64 >= self.config.min_boundary_agreement * num_compared as f64
+    }
+
+    /// Return None unless the reflection of every pixel in the hole is visible.
+    fn reflect_into_hole(
+        image: &BinaryImage,
+        hole_rect: &BoundingRect,
+        contour: &BinaryImage,
+        axis: &ReflectionAxis,
+    ) -> Option<FilledHoleMatrix> {
+        let mut matrix =
+            FilledHoleMatrix::new(hole_rect.width() as usize, hole_rect.height() as usize);
+        for y in 0..matrix.height {
+            for x in 0..matrix.width {
+                let point = PointI32::new(hole_rect.left + x as i32, hole_rect.top + y as i32);
+                let reflected = axis.reflect_pixel(point);
+                if !Self::is_visible(image, hole_rect, reflected) {
+                    return None;
+                }
+                let (rx, ry) = (reflected.x as usize, reflected.y as usize);
+                matrix[PointUsize::new(x, y)] = if contour.get_pixel(rx, ry) {
+                    FilledHoleElement::Structure
+                } else if image.get_pixel(rx, ry) {
+                    FilledHoleElement::Texture
+                } else {
+                    FilledHoleElement::Blank
+                };
+            }
+        }
+        Some(matrix)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn complete_reflects_mirrored_shape_next_to_other_object() {
+        // GIVEN
+        // A triangle mirrored about x = 30, with a hole on its right side,
+        // and a large rectangle far to the right which would bias the centroid of the whole image
+        let (width, height) = (120, 60);
+        let is_triangle = |x: usize, y: usize| {
+            (10..50).contains(&y) && (x as f64 + 0.5 - 30.0).abs() < (y - 10) as f64 / 2.0
+        };
+        let is_rectangle = |x: usize, y: usize| (80..115).contains(&x) && (5..55).contains(&y);
+        let hole_rect = BoundingRect::new_x_y_w_h(38, 30, 8, 10);
+        let mut image = BinaryImage::new_w_h(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                let point = PointI32::new(x as i32, y as i32);
+                let is_in_hole = hole_rect.left <= point.x
+                    && point.x < hole_rect.right
+                    && hole_rect.top <= point.y
+                    && point.y < hole_rect.bottom;
+                image.set_pixel(
+                    x,
+                    y,
+                    !is_in_hole && (is_triangle(x, y) || is_rectangle(x, y)),
+                );
+            }
+        }
+
+        // WHEN
+        let (filled_hole, axis) = SymmetryCompletor::new(SymmetryConfig::default())
+            .complete(&image, hole_rect)
+            .unwrap();
+
+        // THEN
+        assert!(axis.normal.x.abs() > 1.0 - 1e-9);
+        assert!((axis.offset * axis.normal.x - 30.0).abs() < 1e-9);
+        for y in 0..filled_hole.height {
+            for x in 0..filled_hole.width {
+                let (image_x, image_y) = (hole_rect.left as usize + x, hole_rect.top as usize + y);
+                assert_eq!(
+                    filled_hole[y][x] != FilledHoleElement::Blank,
+                    is_triangle(image_x, image_y)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn detect_axis_behind_off_centre_hole() {
+        // GIVEN
+        // A bar with a bump on top, mirrored about x = 120, with a hole hiding most of the bar's right half.
+        // The visible centroid lies about 19 pixels left of the axis.
+        let (width, height) = (240, 200);
+        let is_shape = |x: usize, y: usize| {
+            ((20..220).contains(&x) && (80..120).contains(&y))
+                || ((100..140).contains(&x) && (60..80).contains(&y))
+        };
+        let hole_rect = BoundingRect::new_x_y_w_h(140, 70, 60, 60);
+        let mut image = BinaryImage::new_w_h(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                let is_in_hole =
+                    SymmetryCompletor::is_in_hole(&hole_rect, PointI32::new(x as i32, y as i32));
+                image.set_pixel(x, y, !is_in_hole && is_shape(x, y));
+            }
+        }
+
+        // WHEN
+        let (filled_hole, axis) = SymmetryCompletor::new(SymmetryConfig::default())
+            .complete(&image, hole_rect)
+            .unwrap();
+
+        // THEN
+        assert!(axis.normal.x.abs() > 1.0 - 1e-9);
+        assert!((axis.offset * axis.normal.x - 120.0).abs() < 1e-9);
+        for y in 0..filled_hole.height {
+            for x in 0..filled_hole.width {
+                let (image_x, image_y) = (hole_rect.left as usize + x, hole_rect.top as usize + y);
+                assert!(
+                    (filled_hole[y][x] != FilledHoleElement::Blank) == is_shape(image_x, image_y)
+                );
+            }
+        }
+    }
+}
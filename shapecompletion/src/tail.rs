@@ -0,0 +1,679 @@
+use visioniechor::{PathF64, PointF64};
+
+use crate::{
+    curve::CurveIntrapolatorConfig,
+    geo::{
+        calculate_in_between_point, calculate_midpoint, cross_product,
+        find_corners_open_path_at_scale, find_new_point_from_4_point_scheme,
+        find_new_point_from_centripetal_catmull_rom, fit_polynomial_least_squares,
+        signed_angle_between,
+    },
+};
+
+/// The subdivision scheme used to smooth tail curves.
+/// All schemes keep the two ends and the corners of the path in place.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SmoothingScheme {
+    /// Interpolating 4-point scheme, whose tension is controlled by 'outset_ratio'
+    FourPoint,
+    /// Approximating corner cutting, which replaces each segment by the points at 1/4 and 3/4 of it
+    Chaikin,
+    /// Approximating uniform cubic B-spline subdivision
+    CubicBSpline,
+    /// Interpolating scheme inserting the midpoints of the centripetal Catmull-Rom spline
+    CatmullRom,
+}
+
+/// How the tangent and curvature at a tail are estimated
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TailModel {
+    /// Weighted averages over the path smoothed by 4-point scheme subdivision
+    Subdivision,
+    /// Analytic derivatives of polynomials fitted to the raw path by least squares, parameterized by arc length
+    LeastSquares,
+}
+
+/// The local geometry at the tail (the last point) of an open path.
+pub struct Tail {
+    pub point: PointF64,
+    /// The smoothed path, whose last point is 'point'
+    pub smoothed_path: PathF64,
+    /// The corners of 'smoothed_path' as a bool mask
+    pub corners: Vec<bool>,
+    /// Unit vector at 'point', pointing away from the rest of the path
+    pub tangent: PointF64,
+    /// Signed curvature at 'point' when travelling towards it along the path,
+    /// positive if the path turns in the direction of positive angles
+    pub curvature: f64,
+    /// How reliable 'tangent' is, in [0.0, 1.0]
+    pub confidence: f64,
+    /// Unit vector at 'point' pointing away from the rest of the path,
+    /// if the unsmoothed path is straight from 'point' up to its nearest corner
+    pub straight_direction: Option<PointF64>,
+}
+
+/// Smooth path segments and estimate the geometry at their tails.
+/// Shared by the matching stage and the curve intrapolation stage.
+pub struct TailAnalyzer {
+    pub config: CurveIntrapolatorConfig,
+}
+
+// API
+impl TailAnalyzer {
+    pub fn new(config: CurveIntrapolatorConfig) -> Self {
+        Self { config }
+    }
+
+    /// Smooth 'path' and estimate the tangent at its tail.
+    /// Tangents are averaged over at most 'max_length' along the smoothed path, counting from the tail.
+    /// 'raw_path' is the unsimplified path which 'path' is simplified from, fitted by the least-squares tail model.
+    /// If it is None, 'path' is fitted instead.
+    /// The behavior is undefined unless 'path' is open and has at least 2 points.
+    /// The behavior is also undefined unless 'raw_path', if any, ends at the last point of 'path'.
+    pub fn analyze(&self, path: PathF64, raw_path: Option<&PathF64>, max_length: f64) -> Tail {
+        let point = path[path.len() - 1];
+
+        //# Straightness detection
+        let straight_direction = Self::find_straight_direction_at_tail(
+            &path,
+            raw_path,
+            self.config.corner_threshold,
+            self.config.corner_scale,
+            self.config.straight_tail_tolerance,
+            self.config.straight_tail_min_length,
+        );
+
+        //# Curve smoothing
+        let (smoothed_path, corners) = Self::smooth_open_curve_iterative(
+            path,
+            self.config.smoothing_scheme,
+            self.config.outset_ratio,
+            self.config.min_segment_length,
+            self.config.smooth_max_iterations,
+            self.config.corner_threshold,
+            self.config.corner_scale,
+        );
+
+        //# Tail tangent and curvature approximation
+        let n = std::cmp::min(self.config.tail_tangent_num_points, smoothed_path.len());
+        let least_squares_fit = match self.config.tail_model {
+            TailModel::Subdivision => None,
+            TailModel::LeastSquares => Self::fit_tangent_and_curvature_at_tail(
+                raw_path.unwrap_or(&smoothed_path),
+                self.config.least_squares_num_points,
+                self.config.least_squares_degree,
+                max_length,
+            ),
+        };
+        let (tangent, curvature) = least_squares_fit.unwrap_or_else(|| {
+            (
+                Self::calculate_weighted_average_tangent_at_tail(
+                    &smoothed_path,
+                    &corners,
+                    n,
+                    max_length,
+                    self.config.tail_weight_multiplier,
+                ),
+                Self::calculate_weighted_average_curvature_at_tail(
+                    &smoothed_path,
+                    &corners,
+                    n,
+                    max_length,
+                    self.config.tail_weight_multiplier,
+                ),
+            )
+        });
+
+        //# Tail confidence
+        let confidence = Self::calculate_tail_confidence(
+            &smoothed_path,
+            &corners,
+            n,
+            self.config.confidence_reference_length,
+        );
+
+        Tail {
+            point,
+            smoothed_path,
+            corners,
+            tangent,
+            curvature,
+            confidence,
+            straight_direction,
+        }
+    }
+}
+
+// Helper functions
+impl TailAnalyzer {
+    /// Apply the subdivision by 'smoothing_scheme' on 'path' in a convolutional manner iteratively, preserving corners
+    /// detected at 'corner_scale' (see 'geo::find_corners_open_path_at_scale').
+    /// The corners of the smoothed path are returned as a bool mask.
+    /// Segments (at any point during iteration) shorter than 'min_segment_length' are not further subdivided.
+    /// If no subdivision is performed, the iterative process is terminated early.
+    /// 'path' is returned as-is if path.len() < 4
+    fn smooth_open_curve_iterative(
+        mut path: PathF64,
+        smoothing_scheme: SmoothingScheme,
+        outset_ratio: f64,
+        min_segment_length: f64,
+        max_iterations: usize,
+        corner_threshold: f64,
+        corner_scale: f64,
+    ) -> (PathF64, Vec<bool>) {
+        let mut corners = find_corners_open_path_at_scale(&path, corner_threshold, corner_scale);
+
+        if path.len() < 4 {
+            return (path, corners);
+        }
+
+        for _ in 0..max_iterations {
+            let can_terminate_early = Self::smooth_open_curve_step(
+                &mut path,
+                &mut corners,
+                smoothing_scheme,
+                outset_ratio,
+                min_segment_length,
+            );
+
+            // Early termination
+            if can_terminate_early {
+                break;
+            }
+        }
+
+        (path, corners)
+    }
+
+    /// Return true if no subdivision is done in this step.
+    fn smooth_open_curve_step(
+        path: &mut PathF64,
+        corners: &mut Vec<bool>,
+        smoothing_scheme: SmoothingScheme,
+        outset_ratio: f64,
+        min_segment_length: f64,
+    ) -> bool {
+        let len = corners.len();
+
+        // Duplicate the last point to make sure all segments except the first are subdivided
+        path.add(path[path.len() - 1]);
+
+        // Segment i is from point i to point i+1, the first segment has no point before it
+        let should_subdivide: Vec<bool> = (0..(len - 1))
+            .map(|i| {
+                // Do not smooth out corners
+                // Threshold on segment length of the segment to be broken down
+                i > 0
+                    && !corners[i]
+                    && !corners[i + 1]
+                    && path[i].distance_to(path[i + 1]) >= min_segment_length
+            })
+            .collect();
+        if !should_subdivide.contains(&true) {
+            // no additional points after this step
+            path.path.pop();
+            return true;
+        }
+
+        // Apply the scheme on 'path' in a convolutional manner
+        let mut new_points = vec![];
+        let mut new_corners = vec![];
+        for i in 0..len {
+            // Approximating schemes move a point only if both of its segments are subdivided
+            let is_fixed = i == 0
+                || i == len - 1
+                || corners[i]
+                || !should_subdivide[i - 1]
+                || !should_subdivide[i];
+            match smoothing_scheme {
+                SmoothingScheme::Chaikin if !is_fixed => {} // The point is cut off
+                SmoothingScheme::CubicBSpline if !is_fixed => {
+                    new_points.push((path[i - 1] + path[i] * 6.0 + path[i + 1]) / 8.0);
+                    new_corners.push(false);
+                }
+                _ => {
+                    new_points.push(path[i]);
+                    new_corners.push(corners[i]);
+                }
+            }
+
+            if i == len - 1 || !should_subdivide[i] {
+                continue;
+            }
+            let (p0, p1, p2, p3) = (path[i - 1], path[i], path[i + 1], path[i + 2]);
+            match smoothing_scheme {
+                SmoothingScheme::FourPoint => new_points.push(find_new_point_from_4_point_scheme(
+                    &p1,
+                    &p2,
+                    &p0,
+                    &p3,
+                    outset_ratio,
+                )),
+                SmoothingScheme::Chaikin => {
+                    new_points.push(calculate_in_between_point(p1, p2, 0.25));
+                    new_points.push(calculate_in_between_point(p1, p2, 0.75));
+                }
+                SmoothingScheme::CubicBSpline => new_points.push(calculate_midpoint(p1, p2)),
+                SmoothingScheme::CatmullRom => {
+                    new_points.push(find_new_point_from_centripetal_catmull_rom(p1, p2, p0, p3))
+                }
+            }
+            new_corners.resize(new_points.len(), false); // New points must be non-corners during subdivision
+        }
+
+        assert_eq!(new_points.len(), new_corners.len());
+
+        *path = PathF64::from_points(new_points);
+        *corners = new_corners;
+        false
+    }
+
+    /// Return the direction of the last stretch of 'path' (from its nearest corner to the tail) if that stretch is straight.
+    /// The stretch is straight if it is at least 'min_length' long, and all of its points are within 'tolerance'
+    /// from the line through its two ends.
+    /// If 'raw_path' is not None, the points checked are those of 'raw_path' after its last point nearest to
+    /// the start of the stretch, since simplification removes the deviations of the unsimplified points.
+    /// The behavior is undefined unless 'raw_path', if any, ends at the last point of 'path'.
+    fn find_straight_direction_at_tail(
+        path: &PathF64,
+        raw_path: Option<&PathF64>,
+        corner_threshold: f64,
+        corner_scale: f64,
+        tolerance: f64,
+        min_length: f64,
+    ) -> Option<PointF64> {
+        let corners = find_corners_open_path_at_scale(path, corner_threshold, corner_scale);
+        let len = corners.len();
+        if len < 2 {
+            return None;
+        }
+
+        let stretch_start = (0..(len - 1)).rev().find(|&i| corners[i]).unwrap_or(0);
+        let (from, to) = (path[stretch_start], path[len - 1]);
+        let stretch = to - from;
+        let stretch_length = stretch.norm();
+        if stretch_length < min_length {
+            return None;
+        }
+
+        let direction = stretch / stretch_length;
+        let stretch_points = match raw_path {
+            Some(raw_path) => {
+                // 'min_by' keeps the first of equally near points, which is the last one in reverse order
+                let raw_stretch_start = (0..raw_path.len())
+                    .rev()
+                    .min_by(|&i, &j| {
+                        raw_path[i]
+                            .distance_to(from)
+                            .partial_cmp(&raw_path[j].distance_to(from))
+                            .unwrap()
+                    })
+                    .unwrap_or(0);
+                &raw_path.path[raw_stretch_start..]
+            }
+            None => &path.path[stretch_start..len],
+        };
+        let is_collinear = stretch_points
+            .iter()
+            .all(|&point| cross_product(direction, point - from).abs() <= tolerance);
+
+        if is_collinear {
+            Some(direction)
+        } else {
+            None
+        }
+    }
+
+    /// Calculate the weighted average tangent vector at the tail of 'path'.
+    /// Either the last 'n' points, the most number of points at the tail such that the sum of segment
+    /// lengths is at most base_length, or the last points until a corner is seen, whichever is the smallest,
+    /// are taken into account.
+    /// The weights are stronger towards the tail, this is specified by 'tail_weight_multiplier'.
+    /// The behavior is undefined unless path is open and 1 < n <= path.len().
+    fn calculate_weighted_average_tangent_at_tail(
+        path: &PathF64,
+        corners: &[bool],
+        n: usize,
+        base_length: f64,
+        tail_weight_multiplier: f64,
+    ) -> PointF64 {
+        let len = path.len();
+        assert!(1 < n);
+        assert!(n <= len);
+
+        let mut tangent_acc = PointF64::default();
+        let mut length_acc = 0.0;
+        let rev_points: Vec<PointF64> = path.path.iter().rev().take(n).copied().collect();
+        let rev_corners: Vec<&bool> = corners.iter().rev().take(n).collect();
+        for (i, point_pair) in rev_points.windows(2).enumerate() {
+            // Stop at first corner from tail
+            if *rev_corners[i] {
+                break;
+            }
+
+            let (from, to) = (point_pair[1], point_pair[0]);
+            let from_to = to - from;
+            tangent_acc *= tail_weight_multiplier; // Stronger weights towards the tail (multiplied more times)
+            tangent_acc += from_to.get_normalized();
+
+            length_acc += from_to.norm();
+            if length_acc >= base_length {
+                break;
+            }
+        }
+
+        tangent_acc.get_normalized()
+    }
+
+    /// Calculate the weighted average signed curvature at the tail of 'path'.
+    /// The points taken into account are the same as in 'calculate_weighted_average_tangent_at_tail'.
+    /// The discrete curvature at a point is its turning angle divided by the mean length of its two adjacent segments.
+    /// Return 0.0 if fewer than 3 points are taken into account.
+    /// The behavior is undefined unless path is open and 1 < n <= path.len().
+    fn calculate_weighted_average_curvature_at_tail(
+        path: &PathF64,
+        corners: &[bool],
+        n: usize,
+        base_length: f64,
+        tail_weight_multiplier: f64,
+    ) -> f64 {
+        let mut curvature_acc = 0.0;
+        let mut weight_acc = 0.0;
+        let mut length_acc = 0.0;
+        let rev_points: Vec<PointF64> = path.path.iter().rev().take(n).copied().collect();
+        let rev_corners: Vec<&bool> = corners.iter().rev().take(n).collect();
+        for (i, points) in rev_points.windows(3).enumerate() {
+            // Stop at first corner from tail, the turning angle at a corner is not a curvature
+            if *rev_corners[i] || *rev_corners[i + 1] {
+                break;
+            }
+
+            let (from, mid, to) = (points[2], points[1], points[0]);
+            let (from_mid, mid_to) = (mid - from, to - mid);
+            let mean_segment_length = (from_mid.norm() + mid_to.norm()) / 2.0;
+            if mean_segment_length < f64::EPSILON {
+                continue;
+            }
+
+            // Stronger weights towards the tail (multiplied more times)
+            curvature_acc *= tail_weight_multiplier;
+            weight_acc *= tail_weight_multiplier;
+            curvature_acc += signed_angle_between(from_mid, mid_to) / mean_segment_length;
+            weight_acc += 1.0;
+
+            length_acc += mid_to.norm();
+            if length_acc >= base_length {
+                break;
+            }
+        }
+
+        if weight_acc > 0.0 {
+            curvature_acc / weight_acc
+        } else {
+            0.0
+        }
+    }
+
+    /// Fit polynomials of degree 'degree' in arc length to x and y of the last points of 'path' by least squares,
+    /// and return the tangent and the signed curvature at the tail from their derivatives.
+    /// Either the last 'num_points' points or the most number of points at the tail such that the sum of segment
+    /// lengths is at most 'max_length', whichever is fewer, are fitted.
+    /// Return None if there are too few points for the degree, or if the fit is degenerate.
+    fn fit_tangent_and_curvature_at_tail(
+        path: &PathF64,
+        num_points: usize,
+        degree: usize,
+        max_length: f64,
+    ) -> Option<(PointF64, f64)> {
+        // Arc length from the tail, increasing away from the tail
+        let mut samples = vec![(0.0, path[path.len() - 1])];
+        for point_pair in path.path.iter().rev().collect::<Vec<_>>().windows(2) {
+            if samples.len() >= num_points {
+                break;
+            }
+            let arc_length =
+                samples[samples.len() - 1].0 + point_pair[0].distance_to(*point_pair[1]);
+            if arc_length > max_length {
+                break;
+            }
+            samples.push((arc_length, *point_pair[1]));
+        }
+        if samples.len() < degree + 2 {
+            return None;
+        }
+
+        // Normalize the parameter into [0, 1] for numerical stability
+        let total_length = samples[samples.len() - 1].0;
+        if total_length < f64::EPSILON {
+            return None;
+        }
+        let ts: Vec<f64> = samples.iter().map(|&(s, _)| s / total_length).collect();
+        let xs: Vec<f64> = samples.iter().map(|&(_, point)| point.x).collect();
+        let ys: Vec<f64> = samples.iter().map(|&(_, point)| point.y).collect();
+        let x_coeffs = fit_polynomial_least_squares(&ts, &xs, degree)?;
+        let y_coeffs = fit_polynomial_least_squares(&ts, &ys, degree)?;
+
+        // Derivatives at t = 0 with respect to arc length
+        let coeff = |coeffs: &[f64], i: usize| coeffs.get(i).copied().unwrap_or(0.0);
+        let d1 = PointF64::new(coeff(&x_coeffs, 1), coeff(&y_coeffs, 1)) / total_length;
+        let d2 = PointF64::new(coeff(&x_coeffs, 2), coeff(&y_coeffs, 2)) * 2.0
+            / (total_length * total_length);
+        let speed = d1.norm();
+        if speed < f64::EPSILON {
+            return None;
+        }
+
+        // The parameter increases away from the tail, reverse it to travel towards the tail
+        let tangent = -d1 / speed;
+        let curvature = -cross_product(d1, d2) / (speed * speed * speed);
+        Some((tangent, curvature))
+    }
+
+    /// Estimate how reliable the tail tangent of 'path' is, in [0.0, 1.0].
+    /// The confidence is the product of 3 factors:
+    /// the length of 'path', the straightness of the last 'n' points (until a corner is seen),
+    /// and the distance from the tail to the nearest corner.
+    /// Lengths are compared against 'reference_length', at which a length factor is 0.5.
+    /// The behavior is undefined unless path is open and 1 < n <= path.len().
+    fn calculate_tail_confidence(
+        path: &PathF64,
+        corners: &[bool],
+        n: usize,
+        reference_length: f64,
+    ) -> f64 {
+        let saturate = |length: f64| length / (length + reference_length);
+
+        let rev_points: Vec<PointF64> = path.path.iter().rev().copied().collect();
+        let rev_corners: Vec<&bool> = corners.iter().rev().collect();
+
+        // Length of the whole path and distance to the nearest corner from the tail
+        let mut path_length = 0.0;
+        let mut corner_distance = None;
+        for (i, point_pair) in rev_points.windows(2).enumerate() {
+            if corner_distance.is_none() && *rev_corners[i] {
+                corner_distance = Some(path_length);
+            }
+            path_length += point_pair[0].distance_to(point_pair[1]);
+        }
+
+        // Straightness is the ratio of chord length to arc length
+        let window_last = rev_corners
+            .iter()
+            .take(n)
+            .position(|&&is_corner| is_corner)
+            .unwrap_or(n - 1);
+        let arc_length: f64 = rev_points[..=window_last]
+            .windows(2)
+            .map(|point_pair| point_pair[0].distance_to(point_pair[1]))
+            .sum();
+        let straightness = if arc_length > f64::EPSILON {
+            rev_points[0].distance_to(rev_points[window_last]) / arc_length
+        } else {
+            1.0
+        };
+
+        saturate(path_length) * straightness * corner_distance.map_or(1.0, saturate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Points on the circle of radius 'radius' centred at the origin, from angle 0.0 up to 'sweep_angle',
+    /// travelling in the direction of positive angles if 'sweep_angle' is positive
+    fn circular_arc_path(radius: f64, sweep_angle: f64, num_points: usize) -> PathF64 {
+        PathF64::from_points(
+            (0..num_points)
+                .map(|i| {
+                    let angle = sweep_angle * i as f64 / (num_points - 1) as f64;
+                    PointF64::new(radius * angle.cos(), radius * angle.sin())
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn weighted_average_curvature_of_circular_arc_is_signed_reciprocal_of_radius() {
+        let radius = 20.0;
+        for &sign in [1.0, -1.0].iter() {
+            // GIVEN
+            let path = circular_arc_path(radius, sign * 1.5, 16);
+            let corners = vec![false; path.len()];
+
+            // WHEN
+            let curvature = TailAnalyzer::calculate_weighted_average_curvature_at_tail(
+                &path,
+                &corners,
+                path.len(),
+                f64::MAX,
+                2.0,
+            );
+
+            // THEN
+            assert!(
+                (curvature - sign / radius).abs() < 1e-3,
+                "curvature {} for sign {}",
+                curvature,
+                sign
+            );
+        }
+    }
+
+    #[test]
+    fn least_squares_fit_of_circular_arc_matches_analytic_tangent_and_curvature() {
+        let (radius, sweep_angle) = (20.0, 0.5);
+        for &sign in [1.0, -1.0].iter() {
+            // GIVEN
+            let path = circular_arc_path(radius, sign * sweep_angle, 16);
+            let tail_angle = sign * sweep_angle;
+            let analytic_tangent = PointF64::new(-tail_angle.sin(), tail_angle.cos()) * sign;
+
+            // WHEN
+            let (tangent, curvature) =
+                TailAnalyzer::fit_tangent_and_curvature_at_tail(&path, 16, 3, f64::MAX).unwrap();
+
+            // THEN
+            assert!(
+                signed_angle_between(tangent, analytic_tangent).abs() < 1e-2,
+                "tangent {:?} for sign {}",
+                tangent,
+                sign
+            );
+            assert!(
+                (curvature * radius - sign).abs() < 5e-2,
+                "curvature {} for sign {}",
+                curvature,
+                sign
+            );
+        }
+    }
+
+    #[test]
+    fn straight_direction_is_checked_on_raw_points() {
+        // GIVEN
+        // A path simplified to a corner at (0, 0) followed by a straight stretch along the x-axis,
+        // and two unsimplified versions of it, with and without a 2 pixel wide zigzag along the stretch
+        let path = PathF64::from_points(vec![
+            PointF64::new(0.0, 20.0),
+            PointF64::new(0.0, 0.0),
+            PointF64::new(20.0, 0.0),
+        ]);
+        let raw_path = |zigzag: f64| {
+            let mut points = vec![PointF64::new(0.0, 20.0), PointF64::new(0.0, 10.0)];
+            points.extend((0..=10).map(|i| PointF64::new(2.0 * i as f64, zigzag * (i % 2) as f64)));
+            PathF64::from_points(points)
+        };
+        let find = |raw_path: Option<&PathF64>| {
+            TailAnalyzer::find_straight_direction_at_tail(
+                &path,
+                raw_path,
+                std::f64::consts::FRAC_PI_4,
+                0.0,
+                1.0,
+                8.0,
+            )
+        };
+
+        // WHEN
+        let simplified = find(None);
+        let straight = find(Some(&raw_path(0.0)));
+        let zigzag = find(Some(&raw_path(2.0)));
+
+        // THEN
+        assert_eq!(simplified, Some(PointF64::new(1.0, 0.0)));
+        assert_eq!(straight, Some(PointF64::new(1.0, 0.0)));
+        assert_eq!(zigzag, None);
+    }
+
+    #[test]
+    fn smoothing_schemes_keep_ends_and_corners_in_place() {
+        // GIVEN
+        // A path with a sharp corner at (30, 0)
+        let points = [
+            PointF64::new(0.0, 0.0),
+            PointF64::new(10.0, 3.0),
+            PointF64::new(20.0, 0.0),
+            PointF64::new(30.0, 0.0),
+            PointF64::new(25.0, 10.0),
+            PointF64::new(20.0, 20.0),
+            PointF64::new(10.0, 25.0),
+        ];
+        let corner = points[3];
+
+        for &smoothing_scheme in [
+            SmoothingScheme::FourPoint,
+            SmoothingScheme::Chaikin,
+            SmoothingScheme::CubicBSpline,
+            SmoothingScheme::CatmullRom,
+        ]
+        .iter()
+        {
+            // WHEN
+            let (smoothed_path, corners) = TailAnalyzer::smooth_open_curve_iterative(
+                PathF64::from_points(points.to_vec()),
+                smoothing_scheme,
+                8.0,
+                1.0,
+                3,
+                std::f64::consts::FRAC_PI_2,
+                0.0,
+            );
+
+            // THEN
+            let len = smoothed_path.len();
+            assert!(
+                len > points.len(),
+                "{:?} did not subdivide",
+                smoothing_scheme
+            );
+            assert_eq!(smoothed_path[0], points[0]);
+            assert_eq!(smoothed_path[len - 1], points[points.len() - 1]);
+            let corner_points: Vec<PointF64> = (0..len)
+                .filter(|&i| corners[i])
+                .map(|i| smoothed_path[i])
+                .collect();
+            assert_eq!(corner_points, vec![corner], "{:?}", smoothing_scheme);
+        }
+    }
+}
@@ -0,0 +1,396 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+};
+
+use visioniechor::{BinaryImage, Color, ColorImage, PointI32};
+
+use crate::{
+    filler::{ApplyConfig, FilledHoleElement, FilledHoleMatrix},
+    region::{four_neighbors, label_4_connected_regions},
+};
+
+#[derive(Clone, Copy, Debug)]
+pub struct TextureSynthesisConfig {
+    /// Width and height (in pixels) of the square patches, which should be odd
+    pub patch_size: usize,
+    /// Maximum number of source patches compared for each target patch.
+    /// Larger source regions are subsampled evenly.
+    pub max_candidates: usize,
+}
+
+impl Default for TextureSynthesisConfig {
+    fn default() -> Self {
+        Self {
+            patch_size: 7,
+            max_candidates: 2000,
+        }
+    }
+}
+
+/// Paint the pattern of a shape into a completed hole, by copying patches from the visible part of the same
+/// connected region of the completed shape, in the style of patch-based inpainting.
+/// Texture pixels are copied from the interior of the region, and Structure pixels from its contour,
+/// so that outlines continue along the completed contour.
+pub struct TextureSynthesizer {
+    pub config: TextureSynthesisConfig,
+}
+
+/// The visible pixels which a region of the completed shape is copied from
+#[derive(Default)]
+struct SourcePixels {
+    interior: Vec<PointI32>,
+    contour: Vec<PointI32>,
+}
+
+// API
+impl TextureSynthesizer {
+    pub fn new(config: TextureSynthesisConfig) -> Self {
+        Self { config }
+    }
+
+    /// Return a copy of 'image' with the Texture and Structure pixels of 'filled_hole' painted,
+    /// where 'shape' marks the visible shape with the hole blank, and the top-left corner of the hole is at 'origin'.
+    /// Blank pixels, and pixels of regions without visible pixels, are left unchanged.
+    /// The behavior is undefined unless 'image' and 'shape' are of the same size.
+    pub fn synthesize(
+        &self,
+        image: &ColorImage,
+        shape: &BinaryImage,
+        filled_hole: &FilledHoleMatrix,
+        origin: PointI32,
+    ) -> ColorImage {
+        let completed_shape =
+            filled_hole.applied_to_binary_image(shape, origin, &ApplyConfig::default());
+        let is_in_hole = |point: PointI32| {
+            let offset = point - origin;
+            0 <= offset.x
+                && offset.x < filled_hole.width as i32
+                && 0 <= offset.y
+                && offset.y < filled_hole.height as i32
+        };
+
+        //# Region labelling
+        // Only the labels of foreground pixels are used
+        let (labels, _) = label_4_connected_regions(&completed_shape);
+        let mut sources: HashMap<usize, SourcePixels> = HashMap::new();
+        for y in 0..shape.height as i32 {
+            for x in 0..shape.width as i32 {
+                let point = PointI32::new(x, y);
+                if completed_shape.get_pixel_at(point) && !is_in_hole(point) {
+                    let source = sources
+                        .entry(labels[Self::index_of(shape, point)])
+                        .or_default();
+                    if Self::is_contour_pixel(&completed_shape, point) {
+                        source.contour.push(point);
+                    } else {
+                        source.interior.push(point);
+                    }
+                }
+            }
+        }
+        sources.values_mut().for_each(|source| {
+            source.interior = self.subsample(std::mem::take(&mut source.interior));
+            source.contour = self.subsample(std::mem::take(&mut source.contour));
+        });
+
+        //# Synthesis
+        let mut synthesized = image.clone();
+        let mut is_known = BinaryImage::new_w_h(image.width, image.height);
+        for y in 0..image.height {
+            for x in 0..image.width {
+                is_known.set_pixel(x, y, !is_in_hole(PointI32::new(x as i32, y as i32)));
+            }
+        }
+        let targets: Vec<PointI32> = (0..filled_hole.height)
+            .flat_map(|i| (0..filled_hole.width).map(move |j| (i, j)))
+            .filter(|&(i, j)| filled_hole[i][j] != FilledHoleElement::Blank)
+            .map(|(i, j)| origin + PointI32::new(j as i32, i as i32))
+            .filter(|&point| completed_shape.get_pixel_at_safe(point))
+            .collect();
+        let mut target_ids = vec![None; image.width * image.height];
+        targets
+            .iter()
+            .enumerate()
+            .for_each(|(id, &target)| target_ids[Self::index_of(shape, target)] = Some(id));
+
+        // Fill the target with the most known pixels around it first (the earliest one among equals),
+        // so that patches grow inwards from the visible image.
+        // The queue may hold outdated counts of a target, which are skipped.
+        let mut known_counts: Vec<usize> = targets
+            .iter()
+            .map(|&target| self.count_known_pixels(&is_known, target))
+            .collect();
+        let mut queue: BinaryHeap<(usize, Reverse<usize>)> = known_counts
+            .iter()
+            .enumerate()
+            .map(|(id, &count)| (count, Reverse(id)))
+            .collect();
+
+        while let Some((count, Reverse(id))) = queue.pop() {
+            let target = targets[id];
+            if count != known_counts[id] || is_known.get_pixel_at(target) {
+                continue;
+            }
+
+            let region_sources = sources.get(&labels[Self::index_of(shape, target)]);
+            let candidates = region_sources.map_or(&[][..], |source| {
+                let (preferred, fallback) = if Self::is_contour_pixel(&completed_shape, target) {
+                    (&source.contour, &source.interior)
+                } else {
+                    (&source.interior, &source.contour)
+                };
+                if preferred.is_empty() {
+                    fallback
+                } else {
+                    preferred
+                }
+            });
+
+            let newly_known =
+                match self.find_best_source(&synthesized, &is_known, candidates, target) {
+                    Some(source) => self.copy_patch(
+                        &mut synthesized,
+                        &mut is_known,
+                        &completed_shape,
+                        &labels,
+                        source,
+                        target,
+                    ),
+                    None => {
+                        // Nothing to copy from, so leave the pixel unchanged
+                        is_known.set_pixel_at(target, true);
+                        vec![target]
+                    }
+                };
+
+            // Only the targets whose patches contain a newly known pixel have their counts changed
+            for &point in newly_known.iter() {
+                for offset in self.patch_offsets() {
+                    let neighbor = point - offset;
+                    if !is_known.get_pixel_at_safe(neighbor) {
+                        if let Some(neighbor_id) = Self::target_id_at(shape, &target_ids, neighbor)
+                        {
+                            known_counts[neighbor_id] += 1;
+                            queue.push((known_counts[neighbor_id], Reverse(neighbor_id)));
+                        }
+                    }
+                }
+            }
+        }
+
+        synthesized
+    }
+}
+
+// Helper functions
+impl TextureSynthesizer {
+    fn index_of(image: &BinaryImage, point: PointI32) -> usize {
+        point.y as usize * image.width + point.x as usize
+    }
+
+    /// The id of the target at 'point', or None if 'point' is not a target or is outside the image
+    fn target_id_at(
+        image: &BinaryImage,
+        target_ids: &[Option<usize>],
+        point: PointI32,
+    ) -> Option<usize> {
+        let is_inside_image = 0 <= point.x
+            && point.x < image.width as i32
+            && 0 <= point.y
+            && point.y < image.height as i32;
+        if is_inside_image {
+            target_ids[Self::index_of(image, point)]
+        } else {
+            None
+        }
+    }
+
+    /// A foreground pixel with a background pixel (or the edge of the image) among its 4 neighbors
+    fn is_contour_pixel(image: &BinaryImage, point: PointI32) -> bool {
+        image.get_pixel_at_safe(point)
+            && four_neighbors(point).any(|neighbor| !image.get_pixel_at_safe(neighbor))
+    }
+
+    fn subsample(&self, points: Vec<PointI32>) -> Vec<PointI32> {
+        let stride = points
+            .len()
+            .div_ceil(std::cmp::max(self.config.max_candidates, 1));
+        points
+            .into_iter()
+            .step_by(std::cmp::max(stride, 1))
+            .collect()
+    }
+
+    fn patch_offsets(&self) -> impl Iterator<Item = PointI32> {
+        let radius = (self.config.patch_size / 2) as i32;
+        (-radius..=radius)
+            .flat_map(move |dy| (-radius..=radius).map(move |dx| PointI32::new(dx, dy)))
+    }
+
+    fn count_known_pixels(&self, is_known: &BinaryImage, target: PointI32) -> usize {
+        self.patch_offsets()
+            .filter(|&offset| is_known.get_pixel_at_safe(target + offset))
+            .count()
+    }
+
+    /// Return the candidate whose patch is the most similar to the patch around 'target',
+    /// by the mean squared difference over the pixels known in both patches.
+    fn find_best_source(
+        &self,
+        image: &ColorImage,
+        is_known: &BinaryImage,
+        candidates: &[PointI32],
+        target: PointI32,
+    ) -> Option<PointI32> {
+        candidates
+            .iter()
+            .map(|&candidate| {
+                let (sum, count) = self
+                    .patch_offsets()
+                    .filter(|&offset| {
+                        is_known.get_pixel_at_safe(target + offset)
+                            && is_known.get_pixel_at_safe(candidate + offset)
+                    })
+                    .fold((0.0, 0), |(sum, count), offset| {
+                        let difference = Self::color_difference(
+                            image.get_pixel_at_point_safe(target + offset).unwrap(),
+                            image.get_pixel_at_point_safe(candidate + offset).unwrap(),
+                        );
+                        (sum + difference, count + 1)
+                    });
+                let cost = if count == 0 {
+                    f64::INFINITY
+                } else {
+                    sum / count as f64
+                };
+                (candidate, cost)
+            })
+            .min_by(|(_, cost1), (_, cost2)| cost1.total_cmp(cost2))
+            .map(|(candidate, _)| candidate)
+    }
+
+    fn color_difference(a: Color, b: Color) -> f64 {
+        [(a.r, b.r), (a.g, b.g), (a.b, b.b), (a.a, b.a)]
+            .iter()
+            .map(|&(a, b)| (a as f64 - b as f64).powi(2))
+            .sum()
+    }
+
+    /// Copy the visible pixels of the patch around 'source' to the unknown pixels of the patch around 'target'
+    /// which are in the same region and of the same kind (contour or interior) as 'target'.
+    /// Return the pixels copied to.
+    fn copy_patch(
+        &self,
+        image: &mut ColorImage,
+        is_known: &mut BinaryImage,
+        completed_shape: &BinaryImage,
+        labels: &[usize],
+        source: PointI32,
+        target: PointI32,
+    ) -> Vec<PointI32> {
+        let mut copied = vec![];
+        let target_label = labels[Self::index_of(completed_shape, target)];
+        let is_target_contour = Self::is_contour_pixel(completed_shape, target);
+        for offset in self.patch_offsets() {
+            let (from, to) = (source + offset, target + offset);
+            let should_copy = offset == PointI32::new(0, 0)
+                || (completed_shape.get_pixel_at_safe(to)
+                    && !is_known.get_pixel_at(to)
+                    && labels[Self::index_of(completed_shape, to)] == target_label
+                    && Self::is_contour_pixel(completed_shape, to) == is_target_contour);
+            if !should_copy || !is_known.get_pixel_at_safe(from) {
+                continue;
+            }
+            image.set_pixel(
+                to.x as usize,
+                to.y as usize,
+                &image.get_pixel_at_point_safe(from).unwrap(),
+            );
+            is_known.set_pixel_at(to, true);
+            copied.push(to);
+        }
+        copied
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subsample_keeps_at_least_one_point() {
+        // GIVEN
+        let points: Vec<PointI32> = (0..10).map(|x| PointI32::new(x, 0)).collect();
+        let synthesizer = |max_candidates| {
+            TextureSynthesizer::new(TextureSynthesisConfig {
+                max_candidates,
+                ..Default::default()
+            })
+        };
+
+        // WHEN
+        let none_allowed = synthesizer(0).subsample(points.clone());
+        let some_allowed = synthesizer(4).subsample(points.clone());
+        let empty = synthesizer(4).subsample(vec![]);
+
+        // THEN
+        assert_eq!(none_allowed, vec![PointI32::new(0, 0)]);
+        assert_eq!(
+            some_allowed,
+            vec![
+                PointI32::new(0, 0),
+                PointI32::new(3, 0),
+                PointI32::new(6, 0),
+                PointI32::new(9, 0)
+            ]
+        );
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn synthesize_continues_stripes_into_hole() {
+        // GIVEN
+        // A square shape hatched with vertical stripes of period 4, with a hole inside it
+        let (width, height) = (40, 40);
+        let stripe_color = |x: usize| {
+            if x % 4 < 2 {
+                Color::new(200, 0, 0)
+            } else {
+                Color::new(0, 0, 200)
+            }
+        };
+        let mut image = ColorImage::new_w_h(width, height);
+        let mut shape = BinaryImage::new_w_h(width, height);
+        for y in 5..35 {
+            for x in 5..35 {
+                image.set_pixel(x, y, &stripe_color(x));
+                shape.set_pixel(x, y, true);
+            }
+        }
+        let origin = PointI32::new(15, 15);
+        let mut filled_hole = FilledHoleMatrix::new(10, 10);
+        for i in 0..10 {
+            for j in 0..10 {
+                image.set_pixel(15 + j, 15 + i, &Color::new(255, 255, 255));
+                shape.set_pixel(15 + j, 15 + i, false);
+                filled_hole[i][j] = FilledHoleElement::Texture;
+            }
+        }
+
+        // WHEN
+        let synthesized = TextureSynthesizer::new(Default::default()).synthesize(
+            &image,
+            &shape,
+            &filled_hole,
+            origin,
+        );
+
+        // THEN
+        for y in 15..25 {
+            for x in 15..25 {
+                assert_eq!(synthesized.get_pixel(x, y), stripe_color(x));
+            }
+        }
+    }
+}
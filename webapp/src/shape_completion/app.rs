@@ -42,13 +42,18 @@ impl ShapeCompletorAPI {
             }
         }
 
-        let shape_completor = ShapeCompletor::new(
+        let mut shape_completor = ShapeCompletor::new(
             image,
             config.simplify_tolerance,
             config.curve_intrapolator_config(),
             config.filler_blank_boundary_pixels_tolerance,
             Some(Box::new(draw_util)),
-        );
+        )
+        .with_parameter_scale(config.parameter_scale())
+        .with_fill_mode(config.fill_mode.into());
+        if let Some(symmetry_config) = config.symmetry_config() {
+            shape_completor = shape_completor.with_symmetry_config(symmetry_config);
+        }
 
         let result = shape_completor.complete_shape_and_draw_expandable(hole_rect);
 
@@ -1,7 +1,48 @@
 use super::draw::DisplaySelector;
-use shapecompletion::curve::CurveIntrapolatorConfig;
+use shapecompletion::{
+    completor::ParameterScale, curve::CurveIntrapolatorConfig, filler::FillMode,
+    symmetry::SymmetryConfig, tail::SmoothingScheme,
+};
 use wasm_bindgen::prelude::*;
 
+/// Mirrors 'SmoothingScheme' for the wasm API
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq)]
+pub enum SmoothingSchemeSelector {
+    FourPoint,
+    Chaikin,
+    CubicBSpline,
+    CatmullRom,
+}
+
+impl From<SmoothingSchemeSelector> for SmoothingScheme {
+    fn from(selector: SmoothingSchemeSelector) -> Self {
+        match selector {
+            SmoothingSchemeSelector::FourPoint => Self::FourPoint,
+            SmoothingSchemeSelector::Chaikin => Self::Chaikin,
+            SmoothingSchemeSelector::CubicBSpline => Self::CubicBSpline,
+            SmoothingSchemeSelector::CatmullRom => Self::CatmullRom,
+        }
+    }
+}
+
+/// Mirrors 'FillMode' for the wasm API
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq)]
+pub enum FillModeSelector {
+    BoundarySampling,
+    EvenOdd,
+}
+
+impl From<FillModeSelector> for FillMode {
+    fn from(selector: FillModeSelector) -> Self {
+        match selector {
+            FillModeSelector::BoundarySampling => Self::BoundarySampling,
+            FillModeSelector::EvenOdd => Self::EvenOdd,
+        }
+    }
+}
+
 /// Configuration to ShapeCompletor
 #[wasm_bindgen]
 pub struct ShapeCompletorAPIConfig {
@@ -29,6 +70,16 @@ pub struct ShapeCompletorAPIConfig {
     /// `filler_blank_boundary_pixels_tolerance` or less pixels are blank, then
     /// that subregion will be filled.
     pub filler_blank_boundary_pixels_tolerance: usize,
+    /// How the regions inside the hole are chosen to be filled
+    pub fill_mode: FillModeSelector,
+
+    // Symmetry
+    /// Whether to try completing the hole by mirror symmetry before curve intrapolation
+    pub use_symmetry: bool,
+    symmetry_config: SymmetryConfig,
+
+    // Parameter scale
+    parameter_scale: ParameterScale,
 }
 
 impl Default for ShapeCompletorAPIConfig {
@@ -45,6 +96,10 @@ impl Default for ShapeCompletorAPIConfig {
             simplify_tolerance: 2.0,
             curve_intrapolator_config: Default::default(),
             filler_blank_boundary_pixels_tolerance: 3,
+            fill_mode: FillModeSelector::BoundarySampling,
+            use_symmetry: false,
+            symmetry_config: Default::default(),
+            parameter_scale: ParameterScale::Absolute,
         }
     }
 }
@@ -113,6 +168,11 @@ impl ShapeCompletorAPIConfig {
 
     // CurveInterpolatorConfig
 
+    pub fn curveSmoothingScheme(mut self, value: SmoothingSchemeSelector) -> Self {
+        self.curve_intrapolator_config.smoothing_scheme = value.into();
+        self
+    }
+
     pub fn curveOutsetRatio(mut self, value: f64) -> Self {
         self.curve_intrapolator_config.outset_ratio = value;
         self
@@ -133,6 +193,11 @@ impl ShapeCompletorAPIConfig {
         self
     }
 
+    pub fn curveCornerScale(mut self, value: f64) -> Self {
+        self.curve_intrapolator_config.corner_scale = value;
+        self
+    }
+
     pub fn curveTailTangentNumPoints(mut self, value: usize) -> Self {
         self.curve_intrapolator_config.tail_tangent_num_points = value;
         self
@@ -147,6 +212,96 @@ impl ShapeCompletorAPIConfig {
         self.curve_intrapolator_config.control_points_retract_ratio = value;
         self
     }
+
+    pub fn curveMaxGapPieces(mut self, value: usize) -> Self {
+        self.curve_intrapolator_config.max_gap_pieces = value;
+        self
+    }
+
+    pub fn curveParallelTolerance(mut self, value: f64) -> Self {
+        self.curve_intrapolator_config.parallel_tolerance = value;
+        self
+    }
+
+    pub fn curveRefineControlPoints(mut self, value: bool) -> Self {
+        self.curve_intrapolator_config.refinement_config = if value {
+            Some(Default::default())
+        } else {
+            None
+        };
+        self
+    }
+
+    pub fn curveConfidenceReferenceLength(mut self, value: f64) -> Self {
+        self.curve_intrapolator_config.confidence_reference_length = value;
+        self
+    }
+
+    pub fn curvePreserveCorners(mut self, value: bool) -> Self {
+        self.curve_intrapolator_config.preserve_corners = value;
+        self
+    }
+
+    pub fn curveStraightTailTolerance(mut self, value: f64) -> Self {
+        self.curve_intrapolator_config.straight_tail_tolerance = value;
+        self
+    }
+
+    pub fn curveStraightTailMinLength(mut self, value: f64) -> Self {
+        self.curve_intrapolator_config.straight_tail_min_length = value;
+        self
+    }
+
+    pub fn curveHoleBoundaryTolerance(mut self, value: f64) -> Self {
+        self.curve_intrapolator_config.hole_boundary_tolerance = value;
+        self
+    }
+
+    pub fn curveIntersectionTolerance(mut self, value: f64) -> Self {
+        self.curve_intrapolator_config.intersection_tolerance = value;
+        self
+    }
+
+    pub fn curveMinCurveClearance(mut self, value: f64) -> Self {
+        self.curve_intrapolator_config.min_curve_clearance = value;
+        self
+    }
+
+    // SymmetryConfig
+
+    pub fn useSymmetry(mut self, value: bool) -> Self {
+        self.use_symmetry = value;
+        self
+    }
+
+    pub fn symmetryMinAxisScore(mut self, value: f64) -> Self {
+        self.symmetry_config.min_axis_score = value;
+        self
+    }
+
+    pub fn symmetryMinBoundaryAgreement(mut self, value: f64) -> Self {
+        self.symmetry_config.min_boundary_agreement = value;
+        self
+    }
+
+    // ParameterScale
+
+    pub fn absoluteParameterScale(mut self) -> Self {
+        self.parameter_scale = ParameterScale::Absolute;
+        self
+    }
+
+    pub fn relativeParameterScale(
+        mut self,
+        reference_hole_size: f64,
+        reference_segment_length: f64,
+    ) -> Self {
+        self.parameter_scale = ParameterScale::Relative {
+            reference_hole_size,
+            reference_segment_length,
+        };
+        self
+    }
 }
 
 // API
@@ -158,6 +313,19 @@ impl ShapeCompletorAPIConfig {
     pub fn curve_intrapolator_config(&self) -> CurveIntrapolatorConfig {
         self.curve_intrapolator_config
     }
+
+    pub fn parameter_scale(&self) -> ParameterScale {
+        self.parameter_scale
+    }
+
+    /// Return None if symmetry is not used
+    pub fn symmetry_config(&self) -> Option<SymmetryConfig> {
+        if self.use_symmetry {
+            Some(self.symmetry_config)
+        } else {
+            None
+        }
+    }
 }
 
 // Helper functions